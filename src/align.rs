@@ -1,30 +1,15 @@
-use crate::position::Position;
+use crate::{measure, position::Position};
 use crossterm::style::Attribute;
 use std::str::Split;
-use textwrap::core::display_width;
 
-pub fn get_lines(s: &str) -> (Split<&str>, usize) {
-    let lines = s.split("\n");
-    let mut widest = 0;
-    for line in lines.clone() {
-        // This gives us the printable width of string.
-        // NOTE: printable width is not the same as the number of chars or bytes in a string. When working with Non-ASCII chars it may take up
-        // more than one cell when printed.
-        let w = display_width(line);
-        if w > widest {
-            widest = w;
-        }
-    }
-    return (lines, widest);
+pub fn get_lines(s: &str) -> (Split<'_, &str>, usize) {
+    // NOTE: printable width is not the same as the number of chars or bytes in a string. When working with Non-ASCII chars it may take up
+    // more than one cell when printed.
+    (s.split("\n"), measure::width(s))
 }
 
 pub fn get_strs_height(strs: &str) -> usize {
-    let str_height = strs
-        .chars()
-        .map(|ch| ch == '\n')
-        .fold(0, |acc, x| if x { acc + 1 } else { acc })
-        + 1;
-    str_height
+    measure::height(strs)
 }
 
 pub fn align_text_vertical(strs: &mut String, pos: Position, height: usize) -> String {
@@ -68,13 +53,15 @@ pub fn align_text_horizontal(
     style: Option<&String>,
 ) -> String {
     let (lines, widest_line) = get_lines(strs);
+    // Only the styled spaces need a reset after them, the text already carries its own.
+    let style = style.filter(|st| !st.is_empty());
     let mut temp = String::new();
     for (i, l) in lines.clone().enumerate() {
-        let line_width = display_width(&l);
+        let line_width = measure::width(l);
 
         let mut short_amount = widest_line - line_width;
         if width >= (short_amount + line_width) {
-            short_amount = width - (short_amount + line_width);
+            short_amount += width - (short_amount + line_width);
         }
 
         let mut line = l.to_string();
@@ -112,7 +99,10 @@ pub fn align_text_horizontal(
                 }
             }
         }
-        temp.push_str(&format!("{}{}", line, &Attribute::Reset.to_string()));
+        temp.push_str(&line);
+        if short_amount > 0 && style.is_some() {
+            temp.push_str(&Attribute::Reset.to_string());
+        }
         if i < lines.clone().count() - 1 {
             temp.push_str("\n");
         }
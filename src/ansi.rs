@@ -0,0 +1,72 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::measure::grapheme_width;
+
+pub enum Token<'a> {
+    Escape(&'a str),
+    Char(&'a str, usize),
+}
+
+// Splits the line into escape sequences and grapheme clusters along with their width.
+pub fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut text_start = 0;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, ch)) = chars.next() {
+        if ch != '\x1b' {
+            continue;
+        }
+        push_graphemes(&mut tokens, &line[text_start..i]);
+
+        let mut end = i + 1;
+        match chars.next() {
+            // CSI sequences run until the final byte.
+            Some((j, '[')) => {
+                end = j + 1;
+                for (k, c) in chars.by_ref() {
+                    end = k + c.len_utf8();
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC sequences run until BEL or the string terminator.
+            Some((j, ']')) => {
+                end = j + 1;
+                let mut last = ']';
+                for (k, c) in chars.by_ref() {
+                    end = k + c.len_utf8();
+                    if c == '\x07' || (c == '\\' && last == '\x1b') {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            Some((j, c)) => end = j + c.len_utf8(),
+            None => {}
+        }
+        tokens.push(Token::Escape(&line[i..end]));
+        text_start = end;
+    }
+    push_graphemes(&mut tokens, &line[text_start..]);
+    tokens
+}
+
+fn push_graphemes<'a>(tokens: &mut Vec<Token<'a>>, text: &'a str) {
+    for g in text.graphemes(true) {
+        tokens.push(Token::Char(g, grapheme_width(g)));
+    }
+}
+
+pub fn is_sgr(escape: &str) -> bool {
+    escape.starts_with("\x1b[") && escape.ends_with('m')
+}
+
+// Returns the `params;uri` part of an OSC 8 hyperlink sequence.
+pub fn hyperlink_target(escape: &str) -> Option<&str> {
+    let target = escape.strip_prefix("\x1b]8;")?;
+    let target = target
+        .strip_suffix("\x1b\\")
+        .or_else(|| target.strip_suffix('\x07'))?;
+    Some(target)
+}
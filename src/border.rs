@@ -1,126 +1,395 @@
-use textwrap::core::display_width;
+use std::{borrow::Cow, cmp, error::Error, fmt};
 
-#[derive(Clone, Default, PartialEq)]
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::measure::{self, grapheme_width};
+
+// The parts are `Cow`s so that the presets can be consts, which are copied around without
+// allocating.
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Border {
-    pub top: String,
-    pub bottom: String,
-    pub left: String,
-    pub right: String,
-    pub top_left: String,
-    pub top_right: String,
-    pub bottom_right: String,
-    pub bottom_left: String,
+    pub top: Cow<'static, str>,
+    pub bottom: Cow<'static, str>,
+    pub left: Cow<'static, str>,
+    pub right: Cow<'static, str>,
+    pub top_left: Cow<'static, str>,
+    pub top_right: Cow<'static, str>,
+    pub bottom_right: Cow<'static, str>,
+    pub bottom_left: Cow<'static, str>,
 }
 
-pub fn normal_border() -> Border {
-    Border {
-        top: String::from("─"),
-        bottom: String::from("─"),
-        left: String::from("│"),
-        right: String::from("│"),
-        top_left: String::from("┌"),
-        top_right: String::from("┐"),
-        bottom_left: String::from("└"),
-        bottom_right: String::from("┘"),
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseBorderError {
+    // The spec has to be 8 or 9 graphemes long, holds the length which was given.
+    InvalidLength(usize),
+}
+
+impl fmt::Display for ParseBorderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseBorderError::InvalidLength(n) => {
+                write!(f, "a border spec needs 8 or 9 characters, {} were given", n)
+            }
+        }
     }
 }
 
-pub fn rounded_border() -> Border {
-    Border {
-        top: String::from("─"),
-        bottom: String::from("─"),
-        left: String::from("│"),
-        right: String::from("│"),
-        top_left: String::from("╭"),
-        top_right: String::from("╮"),
-        bottom_left: String::from("╰"),
-        bottom_right: String::from("╯"),
+impl Error for ParseBorderError {}
+
+impl Border {
+    // Reads the border from its chars laid out row by row: `"╭─╮│ │╰─╯"`. The middle char of the
+    // 9 char form is only there to keep the shape and is skipped, the 8 char form leaves it out.
+    pub fn from_spec(spec: &str) -> Result<Self, ParseBorderError> {
+        let mut parts: Vec<&str> = spec.graphemes(true).collect();
+        match parts.len() {
+            9 => {
+                parts.remove(4);
+            }
+            8 => {}
+            n => return Err(ParseBorderError::InvalidLength(n)),
+        }
+        Ok(Border {
+            top_left: parts[0].to_string().into(),
+            top: parts[1].to_string().into(),
+            top_right: parts[2].to_string().into(),
+            left: parts[3].to_string().into(),
+            right: parts[4].to_string().into(),
+            bottom_left: parts[5].to_string().into(),
+            bottom: parts[6].to_string().into(),
+            bottom_right: parts[7].to_string().into(),
+        })
+    }
+
+    pub fn with_top(mut self, value: impl Into<Cow<'static, str>>) -> Self {
+        self.top = value.into();
+        self
+    }
+
+    pub fn with_bottom(mut self, value: impl Into<Cow<'static, str>>) -> Self {
+        self.bottom = value.into();
+        self
+    }
+
+    pub fn with_left(mut self, value: impl Into<Cow<'static, str>>) -> Self {
+        self.left = value.into();
+        self
+    }
+
+    pub fn with_right(mut self, value: impl Into<Cow<'static, str>>) -> Self {
+        self.right = value.into();
+        self
+    }
+
+    pub fn with_top_left(mut self, value: impl Into<Cow<'static, str>>) -> Self {
+        self.top_left = value.into();
+        self
+    }
+
+    pub fn with_top_right(mut self, value: impl Into<Cow<'static, str>>) -> Self {
+        self.top_right = value.into();
+        self
     }
+
+    pub fn with_bottom_left(mut self, value: impl Into<Cow<'static, str>>) -> Self {
+        self.bottom_left = value.into();
+        self
+    }
+
+    pub fn with_bottom_right(mut self, value: impl Into<Cow<'static, str>>) -> Self {
+        self.bottom_right = value.into();
+        self
+    }
+}
+
+pub const NORMAL: Border = Border {
+    top: Cow::Borrowed("─"),
+    bottom: Cow::Borrowed("─"),
+    left: Cow::Borrowed("│"),
+    right: Cow::Borrowed("│"),
+    top_left: Cow::Borrowed("┌"),
+    top_right: Cow::Borrowed("┐"),
+    bottom_left: Cow::Borrowed("└"),
+    bottom_right: Cow::Borrowed("┘"),
+};
+
+pub const ROUNDED: Border = Border {
+    top: Cow::Borrowed("─"),
+    bottom: Cow::Borrowed("─"),
+    left: Cow::Borrowed("│"),
+    right: Cow::Borrowed("│"),
+    top_left: Cow::Borrowed("╭"),
+    top_right: Cow::Borrowed("╮"),
+    bottom_left: Cow::Borrowed("╰"),
+    bottom_right: Cow::Borrowed("╯"),
+};
+
+pub const BLOCK: Border = Border {
+    top: Cow::Borrowed("█"),
+    bottom: Cow::Borrowed("█"),
+    left: Cow::Borrowed("█"),
+    right: Cow::Borrowed("█"),
+    top_left: Cow::Borrowed("█"),
+    top_right: Cow::Borrowed("█"),
+    bottom_left: Cow::Borrowed("█"),
+    bottom_right: Cow::Borrowed("█"),
+};
+
+pub const OUTER_HALF_BLOCK: Border = Border {
+    top: Cow::Borrowed("▀"),
+    bottom: Cow::Borrowed("▄"),
+    left: Cow::Borrowed("▌"),
+    right: Cow::Borrowed("▐"),
+    top_left: Cow::Borrowed("▛"),
+    top_right: Cow::Borrowed("▜"),
+    bottom_left: Cow::Borrowed("▙"),
+    bottom_right: Cow::Borrowed("▟"),
+};
+
+pub const INNER_HALF_BLOCK: Border = Border {
+    top: Cow::Borrowed("▄"),
+    bottom: Cow::Borrowed("▀"),
+    left: Cow::Borrowed("▐"),
+    right: Cow::Borrowed("▌"),
+    top_left: Cow::Borrowed("▗"),
+    top_right: Cow::Borrowed("▖"),
+    bottom_left: Cow::Borrowed("▝"),
+    bottom_right: Cow::Borrowed("▘"),
+};
+
+pub const THICK: Border = Border {
+    top: Cow::Borrowed("━"),
+    bottom: Cow::Borrowed("━"),
+    left: Cow::Borrowed("┃"),
+    right: Cow::Borrowed("┃"),
+    top_left: Cow::Borrowed("┏"),
+    top_right: Cow::Borrowed("┓"),
+    bottom_left: Cow::Borrowed("┗"),
+    bottom_right: Cow::Borrowed("┛"),
+};
+
+pub const DOUBLE: Border = Border {
+    top: Cow::Borrowed("═"),
+    bottom: Cow::Borrowed("═"),
+    left: Cow::Borrowed("║"),
+    right: Cow::Borrowed("║"),
+    top_left: Cow::Borrowed("╔"),
+    top_right: Cow::Borrowed("╗"),
+    bottom_left: Cow::Borrowed("╚"),
+    bottom_right: Cow::Borrowed("╝"),
+};
+
+pub const HIDDEN: Border = Border {
+    top: Cow::Borrowed(" "),
+    bottom: Cow::Borrowed(" "),
+    left: Cow::Borrowed(" "),
+    right: Cow::Borrowed(" "),
+    top_left: Cow::Borrowed(" "),
+    top_right: Cow::Borrowed(" "),
+    bottom_left: Cow::Borrowed(" "),
+    bottom_right: Cow::Borrowed(" "),
+};
+
+// Plain ASCII for consoles without box drawing chars.
+pub const ASCII: Border = Border {
+    top: Cow::Borrowed("-"),
+    bottom: Cow::Borrowed("-"),
+    left: Cow::Borrowed("|"),
+    right: Cow::Borrowed("|"),
+    top_left: Cow::Borrowed("+"),
+    top_right: Cow::Borrowed("+"),
+    bottom_left: Cow::Borrowed("+"),
+    bottom_right: Cow::Borrowed("+"),
+};
+
+pub const DASHED: Border = Border {
+    top: Cow::Borrowed("╌"),
+    bottom: Cow::Borrowed("╌"),
+    left: Cow::Borrowed("╎"),
+    right: Cow::Borrowed("╎"),
+    top_left: Cow::Borrowed("┌"),
+    top_right: Cow::Borrowed("┐"),
+    bottom_left: Cow::Borrowed("└"),
+    bottom_right: Cow::Borrowed("┘"),
+};
+
+pub const DOTTED: Border = Border {
+    top: Cow::Borrowed("┈"),
+    bottom: Cow::Borrowed("┈"),
+    left: Cow::Borrowed("┊"),
+    right: Cow::Borrowed("┊"),
+    top_left: Cow::Borrowed("┌"),
+    top_right: Cow::Borrowed("┐"),
+    bottom_left: Cow::Borrowed("└"),
+    bottom_right: Cow::Borrowed("┘"),
+};
+
+// Double horizontal edges with single vertical ones.
+pub const DOUBLE_SINGLE: Border = Border {
+    top: Cow::Borrowed("═"),
+    bottom: Cow::Borrowed("═"),
+    left: Cow::Borrowed("│"),
+    right: Cow::Borrowed("│"),
+    top_left: Cow::Borrowed("╒"),
+    top_right: Cow::Borrowed("╕"),
+    bottom_left: Cow::Borrowed("╘"),
+    bottom_right: Cow::Borrowed("╛"),
+};
+
+// The outline of a markdown table.
+pub const MARKDOWN: Border = Border {
+    top: Cow::Borrowed("-"),
+    bottom: Cow::Borrowed("-"),
+    left: Cow::Borrowed("|"),
+    right: Cow::Borrowed("|"),
+    top_left: Cow::Borrowed("|"),
+    top_right: Cow::Borrowed("|"),
+    bottom_left: Cow::Borrowed("|"),
+    bottom_right: Cow::Borrowed("|"),
+};
+
+pub fn normal_border() -> Border {
+    NORMAL
+}
+
+pub fn rounded_border() -> Border {
+    ROUNDED
 }
 
 pub fn block_border() -> Border {
-    Border {
-        top: String::from("█"),
-        bottom: String::from("█"),
-        left: String::from("█"),
-        right: String::from("█"),
-        top_left: String::from("█"),
-        top_right: String::from("█"),
-        bottom_left: String::from("█"),
-        bottom_right: String::from("█"),
-    }
+    BLOCK
 }
 
 pub fn outer_half_block_border() -> Border {
-    Border {
-        top: String::from("▀"),
-        bottom: String::from("▄"),
-        left: String::from("▌"),
-        right: String::from("▐"),
-        top_left: String::from("▛"),
-        top_right: String::from("▜"),
-        bottom_left: String::from("▙"),
-        bottom_right: String::from("▟"),
-    }
+    OUTER_HALF_BLOCK
 }
 
 pub fn inner_half_block_border() -> Border {
-    Border {
-        top: "▄".into(),
-        bottom: "▀".into(),
-        left: "▐".into(),
-        right: "▌".into(),
-        top_left: "▗".into(),
-        top_right: "▖".into(),
-        bottom_left: "▝".into(),
-        bottom_right: "▘".into(),
-    }
+    INNER_HALF_BLOCK
 }
 
 pub fn thick_border() -> Border {
-    Border {
-        top: "━".into(),
-        bottom: "━".into(),
-        left: "┃".into(),
-        right: "┃".into(),
-        top_left: "┏".into(),
-        top_right: "┓".into(),
-        bottom_left: "┗".into(),
-        bottom_right: "┛".into(),
-    }
+    THICK
 }
 
 pub fn double_border() -> Border {
-    Border {
-        top: "═".to_string(),
-        bottom: "═".to_string(),
-        left: "║".to_string(),
-        right: "║".to_string(),
-        top_left: "╔".to_string(),
-        top_right: "╗".to_string(),
-        bottom_left: "╚".to_string(),
-        bottom_right: "╝".to_string(),
-    }
+    DOUBLE
 }
 
 pub fn hidden_border() -> Border {
-    Border {
-        top: " ".into(),
-        bottom: " ".into(),
-        left: " ".into(),
-        right: " ".into(),
-        top_left: " ".into(),
-        top_right: " ".into(),
-        bottom_left: " ".into(),
-        bottom_right: " ".into(),
+    HIDDEN
+}
+
+pub fn ascii_border() -> Border {
+    ASCII
+}
+
+pub fn dashed_border() -> Border {
+    DASHED
+}
+
+pub fn dotted_border() -> Border {
+    DOTTED
+}
+
+pub fn double_single_border() -> Border {
+    DOUBLE_SINGLE
+}
+
+pub fn markdown_border() -> Border {
+    MARKDOWN
+}
+
+// Box drawing chars along with the weight of their arms going up, right, down and left. 1 is a
+// light line, 2 a heavy one and 3 a double one.
+const BOX_CHARS: [(char, [u8; 4]); 37] = [
+    ('─', [0, 1, 0, 1]),
+    ('│', [1, 0, 1, 0]),
+    ('┌', [0, 1, 1, 0]),
+    ('┐', [0, 0, 1, 1]),
+    ('└', [1, 1, 0, 0]),
+    ('┘', [1, 0, 0, 1]),
+    ('├', [1, 1, 1, 0]),
+    ('┤', [1, 0, 1, 1]),
+    ('┬', [0, 1, 1, 1]),
+    ('┴', [1, 1, 0, 1]),
+    ('┼', [1, 1, 1, 1]),
+    ('╭', [0, 1, 1, 0]),
+    ('╮', [0, 0, 1, 1]),
+    ('╰', [1, 1, 0, 0]),
+    ('╯', [1, 0, 0, 1]),
+    ('━', [0, 2, 0, 2]),
+    ('┃', [2, 0, 2, 0]),
+    ('┏', [0, 2, 2, 0]),
+    ('┓', [0, 0, 2, 2]),
+    ('┗', [2, 2, 0, 0]),
+    ('┛', [2, 0, 0, 2]),
+    ('┣', [2, 2, 2, 0]),
+    ('┫', [2, 0, 2, 2]),
+    ('┳', [0, 2, 2, 2]),
+    ('┻', [2, 2, 0, 2]),
+    ('╋', [2, 2, 2, 2]),
+    ('═', [0, 3, 0, 3]),
+    ('║', [3, 0, 3, 0]),
+    ('╔', [0, 3, 3, 0]),
+    ('╗', [0, 0, 3, 3]),
+    ('╚', [3, 3, 0, 0]),
+    ('╝', [3, 0, 0, 3]),
+    ('╠', [3, 3, 3, 0]),
+    ('╣', [3, 0, 3, 3]),
+    ('╦', [0, 3, 3, 3]),
+    ('╩', [3, 3, 0, 3]),
+    ('╬', [3, 3, 3, 3]),
+];
+
+// Merges two box drawing chars drawn over each other into the junction which has the arms of
+// both, like `│` and `─` into `┼`. Returns `None` when either one is not a box drawing char, when
+// there is no char for the mix of weights or when `top` already has all the arms.
+pub fn merge_junction(under: &str, top: &str) -> Option<String> {
+    let arms = |symbol: &str| {
+        let mut chars = symbol.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => BOX_CHARS.iter().find(|(c, _)| *c == ch).map(|(_, a)| *a),
+            _ => None,
+        }
+    };
+    let (a, b) = (arms(under)?, arms(top)?);
+    let mut merged = [0; 4];
+    for i in 0..4 {
+        merged[i] = cmp::max(a[i], b[i]);
+    }
+    if merged == b {
+        return None;
     }
+    BOX_CHARS
+        .iter()
+        .find(|(_, arms)| *arms == merged)
+        .map(|(c, _)| c.to_string())
 }
 
-pub fn get_first_char_as_string(strs: &str) -> String {
-    if let Some(ch) = strs.chars().next() {
-        return ch.to_string();
+// The width of a column of the frame, which is the widest of its corners and side pieces.
+pub fn column_width(top: &str, side: &str, bottom: &str) -> usize {
+    let side_width = side.graphemes(true).map(grapheme_width).max().unwrap_or(0);
+    cmp::max(
+        side_width,
+        cmp::max(measure::width(top), measure::width(bottom)),
+    )
+}
+
+// Stretches the corner to `width` with the edge pattern, on its left for the right hand corners.
+pub fn pad_corner(
+    corner: Cow<'static, str>,
+    edge: &str,
+    width: usize,
+    right: bool,
+) -> Cow<'static, str> {
+    let n = width.saturating_sub(measure::width(&corner));
+    if n == 0 {
+        return corner;
+    }
+    match right {
+        true => format!("{}{}", render_horizontal_edge("", edge, "", n), corner).into(),
+        false => format!("{}{}", corner, render_horizontal_edge("", edge, "", n)).into(),
     }
-    return String::from("");
 }
 
 pub fn render_horizontal_edge(left: &str, mut middle: &str, right: &str, width: usize) -> String {
@@ -133,23 +402,96 @@ pub fn render_horizontal_edge(left: &str, mut middle: &str, right: &str, width:
         middle = " ";
     }
 
-    let left_width = display_width(left);
-    let right_width = display_width(right);
-
     let mut j = 0;
-    let mut i = left_width + right_width;
-    let chars: Vec<String> = middle.chars().map(|c| c.to_string()).collect();
+    let mut i = 0;
+    let graphemes: Vec<&str> = middle.graphemes(true).collect();
     compiled_string.push_str(left);
 
-    while i < (left_width + width + right_width) {
-        compiled_string.push_str(&chars[j]);
+    while i < width {
+        // Wide graphemes which don't fit in the remaining space are replaced with spaces below.
+        let w = grapheme_width(graphemes[j]);
+        if w == 0 || i + w > width {
+            break;
+        }
+        compiled_string.push_str(graphemes[j]);
+        i += w;
         j += 1;
-        if j >= chars.len() {
+        if j >= graphemes.len() {
             j = 0
         }
-        i += display_width(&chars[j].to_string());
+    }
+    if i < width {
+        compiled_string.push_str(&" ".repeat(width - i));
     }
     compiled_string.push_str(right);
 
     compiled_string
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_spec_skips_the_middle_of_nine_chars() {
+        assert_eq!(Border::from_spec("╭─╮│ │╰─╯"), Ok(ROUNDED));
+        assert_eq!(Border::from_spec("╭─╮│x│╰─╯"), Ok(ROUNDED));
+    }
+
+    #[test]
+    fn from_spec_reads_eight_chars() {
+        assert_eq!(Border::from_spec("┏━┓┃┃┗━┛"), Ok(THICK));
+        let border = Border::from_spec("1234567８").unwrap();
+        let parts = [
+            &border.top_left,
+            &border.top,
+            &border.top_right,
+            &border.left,
+            &border.right,
+            &border.bottom_left,
+            &border.bottom,
+            &border.bottom_right,
+        ];
+        assert_eq!(parts, ["1", "2", "3", "4", "5", "6", "7", "８"]);
+    }
+
+    #[test]
+    fn from_spec_rejects_other_lengths() {
+        assert_eq!(
+            Border::from_spec("╭─╮│╰─╯").unwrap_err(),
+            ParseBorderError::InvalidLength(7)
+        );
+        assert_eq!(
+            Border::from_spec("").unwrap_err(),
+            ParseBorderError::InvalidLength(0)
+        );
+        // Graphemes are counted, not chars.
+        assert_eq!(
+            Border::from_spec("👩‍💻─╮│ │╰─╯x").unwrap_err(),
+            ParseBorderError::InvalidLength(10)
+        );
+    }
+
+    #[test]
+    fn corners_and_edges_merge_into_junctions() {
+        assert_eq!(merge_junction("┐", "┌").as_deref(), Some("┬"));
+        assert_eq!(merge_junction("┘", "└").as_deref(), Some("┴"));
+        assert_eq!(merge_junction("┤", "├").as_deref(), Some("┼"));
+        assert_eq!(merge_junction("│", "─").as_deref(), Some("┼"));
+        assert_eq!(merge_junction("╮", "╭").as_deref(), Some("┬"));
+        assert_eq!(merge_junction("╗", "╔").as_deref(), Some("╦"));
+        assert_eq!(merge_junction("║", "═").as_deref(), Some("╬"));
+    }
+
+    #[test]
+    fn nothing_to_merge() {
+        // The top already has every arm.
+        assert_eq!(merge_junction("─", "┼"), None);
+        assert_eq!(merge_junction("┌", "┌"), None);
+        // There is no char for light and heavy arms mixed together.
+        assert_eq!(merge_junction("┃", "─"), None);
+        assert_eq!(merge_junction("a", "─"), None);
+        assert_eq!(merge_junction("│", " "), None);
+        assert_eq!(merge_junction("──", "│"), None);
+    }
+}
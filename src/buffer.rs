@@ -0,0 +1,581 @@
+use std::cmp;
+
+use crossterm::style::{Attribute, Attributes, Color, Colored};
+
+use crate::{
+    ansi::{hyperlink_target, is_sgr, tokenize, Token},
+    style::UnderlineStyle,
+    whitespace::WhiteSpace,
+};
+
+// Attributes which can be switched on, along with the SGR codes which switch them off.
+const ATTRIBUTES: [(Attribute, &[u16]); 10] = [
+    (Attribute::Bold, &[21, 22]),
+    (Attribute::Dim, &[22]),
+    (Attribute::Italic, &[23]),
+    (Attribute::Underlined, &[24]),
+    (Attribute::SlowBlink, &[25]),
+    (Attribute::RapidBlink, &[25]),
+    (Attribute::Reverse, &[27]),
+    (Attribute::Hidden, &[28]),
+    (Attribute::CrossedOut, &[29]),
+    (Attribute::OverLined, &[55]),
+];
+
+const NAMED_COLORS: [Color; 8] = [
+    Color::Black,
+    Color::DarkRed,
+    Color::DarkGreen,
+    Color::DarkYellow,
+    Color::DarkBlue,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+    Color::Grey,
+];
+
+const BRIGHT_COLORS: [Color; 8] = [
+    Color::DarkGrey,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CellStyle {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub attributes: Attributes,
+    pub underline_style: UnderlineStyle,
+    pub underline_color: Option<Color>,
+    pub link: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cell {
+    pub symbol: String,
+    pub width: usize,
+    pub style: CellStyle,
+}
+
+// A block of text as lines of cells. Every cell takes up exactly one column, the cells after a
+// wide grapheme are continuation cells with an empty symbol. Lines can be shorter than the width.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Buffer {
+    pub width: usize,
+    pub lines: Vec<Vec<Cell>>,
+}
+
+impl CellStyle {
+    fn apply_sgr(&mut self, escape: &str) {
+        let raw: Vec<&str> = escape[2..escape.len() - 1].split(';').collect();
+        let params: Vec<u16> = raw
+            .iter()
+            .map(|p| p.split(':').next().unwrap_or("").parse().unwrap_or(0))
+            .collect();
+
+        let mut i = 0;
+        while i < params.len() {
+            match params[i] {
+                0 => {
+                    let link = self.link.take();
+                    *self = CellStyle::default();
+                    self.link = link;
+                }
+                code @ (30..=37) => self.fg = Some(NAMED_COLORS[(code - 30) as usize]),
+                code @ (40..=47) => self.bg = Some(NAMED_COLORS[(code - 40) as usize]),
+                code @ (90..=97) => self.fg = Some(BRIGHT_COLORS[(code - 90) as usize]),
+                code @ (100..=107) => self.bg = Some(BRIGHT_COLORS[(code - 100) as usize]),
+                39 => self.fg = None,
+                49 => self.bg = None,
+                59 => self.underline_color = None,
+                // `4:x` picks the underline style, `4:0` switches the underline off.
+                4 => match raw[i]
+                    .split_once(':')
+                    .map(|(_, x)| UnderlineStyle::from_sgr(x))
+                {
+                    Some(None) => self.attributes.unset(Attribute::Underlined),
+                    Some(Some(style)) => {
+                        self.attributes.set(Attribute::Underlined);
+                        self.underline_style = style;
+                    }
+                    None => {
+                        self.attributes.set(Attribute::Underlined);
+                        self.underline_style = UnderlineStyle::Single;
+                    }
+                },
+                code @ (38 | 48 | 58) => {
+                    let (color, used) = match raw[i].contains(':') {
+                        true => (parse_color_subparams(raw[i]), 0),
+                        false => parse_color(&params[i + 1..]),
+                    };
+                    match code {
+                        38 => self.fg = color.or(self.fg),
+                        48 => self.bg = color.or(self.bg),
+                        _ => self.underline_color = color.or(self.underline_color),
+                    }
+                    i += used;
+                }
+                code => {
+                    for (attr, off) in ATTRIBUTES {
+                        if attr.sgr() as u16 == code {
+                            self.attributes.set(attr);
+                        } else if off.contains(&code) {
+                            self.attributes.unset(attr);
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+    }
+
+    fn apply(&mut self, escape: &str) {
+        if is_sgr(escape) {
+            self.apply_sgr(escape);
+        } else if let Some(target) = hyperlink_target(escape) {
+            self.link = match target.split_once(';') {
+                Some((_, "")) | None => None,
+                Some(_) => Some(target.to_string()),
+            };
+        }
+    }
+
+    // The escape sequences which take the terminal from this style to `next`. Only the changed
+    // parts are written, unless something has to be switched off in which case the style is
+    // reset and written again.
+    pub fn transition(&self, next: &CellStyle) -> String {
+        let mut b = String::new();
+        if self.link != next.link {
+            if self.link.is_some() {
+                b.push_str("\x1b]8;;\x1b\\");
+            }
+            if let Some(target) = &next.link {
+                b.push_str(&format!("\x1b]8;{}\x1b\\", target));
+            }
+        }
+
+        let needs_reset = (self.fg.is_some() && next.fg.is_none())
+            || (self.bg.is_some() && next.bg.is_none())
+            || (self.underline_color.is_some() && next.underline_color.is_none())
+            || ATTRIBUTES
+                .iter()
+                .any(|(attr, _)| self.attributes.has(*attr) && !next.attributes.has(*attr));
+
+        let mut params = vec![];
+        let mut current = self;
+        let default = CellStyle::default();
+        if needs_reset {
+            params.push(String::from("0"));
+            current = &default;
+        }
+        for (attr, _) in ATTRIBUTES {
+            if attr == Attribute::Underlined {
+                let changed = !current.attributes.has(attr)
+                    || current.underline_style != next.underline_style;
+                if next.attributes.has(attr) && changed {
+                    params.push(next.underline_style.sgr().to_string());
+                }
+            } else if next.attributes.has(attr) && !current.attributes.has(attr) {
+                params.push(attr.sgr().to_string());
+            }
+        }
+        if let Some(fg) = next.fg {
+            if current.fg != next.fg {
+                params.push(color_param(fg, true));
+            }
+        }
+        if let Some(bg) = next.bg {
+            if current.bg != next.bg {
+                params.push(color_param(bg, false));
+            }
+        }
+        if let Some(color) = next.underline_color {
+            if current.underline_color != next.underline_color {
+                params.push(underline_color_param(color));
+            }
+        }
+
+        if !params.is_empty() {
+            b.push_str(&format!("\x1b[{}m", params.join(";")));
+        }
+        b
+    }
+}
+
+impl Cell {
+    pub fn new(symbol: &str, width: usize, style: CellStyle) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            width,
+            style,
+        }
+    }
+
+    pub fn blank() -> Self {
+        Self::new(" ", 1, CellStyle::default())
+    }
+
+    fn continuation(style: CellStyle) -> Self {
+        Self::new("", 0, style)
+    }
+
+    pub fn is_continuation(&self) -> bool {
+        self.width == 0
+    }
+}
+
+impl Buffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            lines: vec![vec![Cell::blank(); width]; height],
+        }
+    }
+
+    // Parses the rendered string. The SGR state carries over from one line to the next just like
+    // it does on a terminal. Other escape sequences are kept in the symbol of the cell before them,
+    // or of the first cell on the line when nothing comes before them. Lines without any cells drop
+    // them.
+    pub fn from_ansi(strs: &str) -> Self {
+        let mut lines = vec![];
+        let mut style = CellStyle::default();
+        for l in strs.split('\n') {
+            let mut line: Vec<Cell> = vec![];
+            let mut pending = String::new();
+            for token in tokenize(l) {
+                match token {
+                    Token::Escape(e) if is_sgr(e) || hyperlink_target(e).is_some() => {
+                        style.apply(e)
+                    }
+                    // Zero width graphemes such as control chars are kept with the grapheme before them.
+                    Token::Escape(c) | Token::Char(c, 0) => {
+                        match line.iter_mut().rev().find(|c| !c.is_continuation()) {
+                            Some(head) => head.symbol.push_str(c),
+                            None => pending.push_str(c),
+                        }
+                    }
+                    Token::Char(c, w) => {
+                        line.push(Cell::new(
+                            &format!("{}{}", std::mem::take(&mut pending), c),
+                            w,
+                            style.clone(),
+                        ));
+                        for _ in 1..w {
+                            line.push(Cell::continuation(style.clone()));
+                        }
+                    }
+                }
+            }
+            lines.push(line);
+        }
+        let width = lines.iter().map(|l| l.len()).max().unwrap_or(0);
+        Self { width, lines }
+    }
+
+    pub fn height(&self) -> usize {
+        self.lines.len()
+    }
+
+    // Pads the short lines with blank cells up to the width of the buffer.
+    pub fn pad_lines(&mut self) {
+        for line in &mut self.lines {
+            while line.len() < self.width {
+                line.push(Cell::blank());
+            }
+        }
+    }
+
+    // Fills every line of the buffer with the whitespace, replacing whatever was there.
+    pub fn fill(&mut self, ws: &mut WhiteSpace) {
+        let filler = Buffer::from_ansi(&ws.render(self.width));
+        for line in &mut self.lines {
+            *line = filler.lines[0].clone();
+        }
+    }
+
+    // Writes the cell at column `x` of line `y`. Wide graphemes which get cut by the write, or which
+    // don't fit before the right edge, are replaced with spaces.
+    pub fn set(&mut self, x: usize, y: usize, cell: Cell) {
+        if y >= self.lines.len() || x >= self.width || cell.is_continuation() {
+            return;
+        }
+        let width = self.width;
+        let line = &mut self.lines[y];
+        while line.len() < x {
+            line.push(Cell::blank());
+        }
+
+        let w = cell.width;
+        let end = cmp::min(x + w, width);
+        if x > 0 && x < line.len() && line[x].is_continuation() {
+            line[x - 1] = Cell::new(" ", 1, line[x - 1].style.clone());
+        }
+        if end < line.len() && line[end].is_continuation() {
+            line[end] = Cell::new(" ", 1, line[end].style.clone());
+        }
+
+        if x + w > width {
+            for i in x..width {
+                put(line, i, Cell::new(" ", 1, cell.style.clone()));
+            }
+            return;
+        }
+        let style = cell.style.clone();
+        put(line, x, cell);
+        for i in 1..w {
+            put(line, x + i, Cell::continuation(style.clone()));
+        }
+    }
+
+    pub fn blit_line(&mut self, line: &[Cell], x: usize, y: usize) {
+        for (i, cell) in line.iter().enumerate() {
+            self.set(x + i, y, cell.clone());
+        }
+    }
+
+    // Draws the other buffer with its top left corner at column `x` and line `y`. Whatever falls
+    // outside of this buffer is cut off.
+    pub fn blit(&mut self, other: &Buffer, x: usize, y: usize) {
+        for (i, line) in other.lines.iter().enumerate() {
+            self.blit_line(line, x, y + i);
+        }
+    }
+
+    pub fn to_ansi(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line_to_ansi(line))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+// Writes the cells out with only the style changes between them, ending with the default style.
+pub fn line_to_ansi(line: &[Cell]) -> String {
+    let default = CellStyle::default();
+    let mut b = String::new();
+    let mut current = &default;
+    for cell in line {
+        if cell.is_continuation() {
+            continue;
+        }
+        b.push_str(&current.transition(&cell.style));
+        b.push_str(&cell.symbol);
+        current = &cell.style;
+    }
+    b.push_str(&current.transition(&default));
+    b
+}
+
+// Rewrites the SGR and hyperlink sequences of an already rendered string so that only the changes
+// in style are written, right before the text which needs them. Other escape sequences are kept
+// as they are.
+pub fn optimize_sgr(strs: &str) -> String {
+    let mut b = String::new();
+    let mut current = CellStyle::default();
+    let mut pending = CellStyle::default();
+    for token in tokenize(strs) {
+        match token {
+            Token::Escape(e) if is_sgr(e) || hyperlink_target(e).is_some() => pending.apply(e),
+            Token::Escape(e) => {
+                b.push_str(&current.transition(&pending));
+                current = pending.clone();
+                b.push_str(e);
+            }
+            Token::Char(c, _) => {
+                b.push_str(&current.transition(&pending));
+                current = pending.clone();
+                b.push_str(c);
+            }
+        }
+    }
+    b.push_str(&current.transition(&pending));
+    b
+}
+
+fn put(line: &mut Vec<Cell>, x: usize, cell: Cell) {
+    if x < line.len() {
+        line[x] = cell;
+    } else {
+        line.push(cell);
+    }
+}
+
+// The 16 named colors have their own short codes, everything else goes through crossterm.
+fn color_param(color: Color, foreground: bool) -> String {
+    let (base, bright_base, colored) = match foreground {
+        true => (30, 90, Colored::ForegroundColor(color)),
+        false => (40, 100, Colored::BackgroundColor(color)),
+    };
+    if let Some(i) = NAMED_COLORS.iter().position(|c| *c == color) {
+        return (base + i).to_string();
+    }
+    if let Some(i) = BRIGHT_COLORS.iter().position(|c| *c == color) {
+        return (bright_base + i).to_string();
+    }
+    colored.to_string()
+}
+
+// Underline colors have no short codes, the named colors go by their 256 color index.
+pub(crate) fn underline_color_param(color: Color) -> String {
+    if let Some(i) = NAMED_COLORS.iter().position(|c| *c == color) {
+        return format!("58;5;{}", i);
+    }
+    if let Some(i) = BRIGHT_COLORS.iter().position(|c| *c == color) {
+        return format!("58;5;{}", i + 8);
+    }
+    match color {
+        Color::Rgb { r, g, b } => format!("58;2;{};{};{}", r, g, b),
+        Color::AnsiValue(n) => format!("58;5;{}", n),
+        _ => String::from("59"),
+    }
+}
+
+// Parses the `38:5:n` and `38:2::r:g:b` forms, where the color is given in sub params.
+fn parse_color_subparams(param: &str) -> Option<Color> {
+    let subs: Vec<u16> = param
+        .split(':')
+        .skip(1)
+        .filter(|p| !p.is_empty())
+        .map(|p| p.parse().unwrap_or(0))
+        .collect();
+    match subs.as_slice() {
+        [2, .., r, g, b] => parse_color(&[2, *r, *g, *b]).0,
+        [5, n] => parse_color(&[5, *n]).0,
+        _ => None,
+    }
+}
+
+// Parses the `5;n` and `2;r;g;b` forms which follow 38 and 48. Returns the color along with the
+// number of params used.
+fn parse_color(params: &[u16]) -> (Option<Color>, usize) {
+    match params {
+        [5, n, ..] => (Color::parse_ansi(&format!("5;{}", n)), 2),
+        [2, r, g, b, ..] => (
+            Some(Color::Rgb {
+                r: *r as u8,
+                g: *g as u8,
+                b: *b as u8,
+            }),
+            4,
+        ),
+        _ => (None, params.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(escapes: &[&str]) -> CellStyle {
+        let mut style = CellStyle::default();
+        for e in escapes {
+            style.apply(e);
+        }
+        style
+    }
+
+    #[test]
+    fn sgr_sets_attributes_and_colors() {
+        let style = parsed(&["\x1b[1;31;48;5;21m"]);
+        assert!(style.attributes.has(Attribute::Bold));
+        assert_eq!(style.fg, Some(Color::DarkRed));
+        assert_eq!(style.bg, Some(Color::AnsiValue(21)));
+
+        let style = parsed(&["\x1b[38;2;1;2;3;4m"]);
+        assert_eq!(style.fg, Some(Color::Rgb { r: 1, g: 2, b: 3 }));
+        assert!(style.attributes.has(Attribute::Underlined));
+    }
+
+    #[test]
+    fn sgr_switches_things_off() {
+        let style = parsed(&["\x1b[1;2;3;32m", "\x1b[22;39m"]);
+        assert!(!style.attributes.has(Attribute::Bold));
+        assert!(!style.attributes.has(Attribute::Dim));
+        assert!(style.attributes.has(Attribute::Italic));
+        assert_eq!(style.fg, None);
+
+        assert_eq!(parsed(&["\x1b[1;32m", "\x1b[m"]), CellStyle::default());
+    }
+
+    #[test]
+    fn reset_keeps_the_hyperlink() {
+        let style = parsed(&["\x1b]8;;https://example.com\x1b\\", "\x1b[1m", "\x1b[0m"]);
+        assert_eq!(style.link.as_deref(), Some(";https://example.com"));
+        assert_eq!(style.attributes, Attributes::default());
+    }
+
+    #[test]
+    fn underline_styles_and_colors() {
+        let style = parsed(&["\x1b[4:3;58:2::255:0:0m"]);
+        assert!(style.attributes.has(Attribute::Underlined));
+        assert_eq!(style.underline_style, UnderlineStyle::Curly);
+        assert_eq!(
+            style.underline_color,
+            Some(Color::Rgb { r: 255, g: 0, b: 0 })
+        );
+
+        let style = parsed(&["\x1b[4:3;58;5;1m", "\x1b[4:0;59m"]);
+        assert!(!style.attributes.has(Attribute::Underlined));
+        assert_eq!(style.underline_color, None);
+    }
+
+    #[test]
+    fn transition_writes_only_the_changes() {
+        let bold = parsed(&["\x1b[1m"]);
+        let bold_red = parsed(&["\x1b[1;31m"]);
+        assert_eq!(CellStyle::default().transition(&bold), "\x1b[1m");
+        assert_eq!(bold.transition(&bold_red), "\x1b[31m");
+        assert_eq!(bold_red.transition(&bold_red), "");
+    }
+
+    #[test]
+    fn transition_resets_when_something_is_switched_off() {
+        let bold_red = parsed(&["\x1b[1;31m"]);
+        let red = parsed(&["\x1b[31m"]);
+        assert_eq!(bold_red.transition(&red), "\x1b[0;31m");
+        assert_eq!(red.transition(&CellStyle::default()), "\x1b[0m");
+    }
+
+    #[test]
+    fn transition_switches_hyperlinks() {
+        let linked = parsed(&["\x1b]8;;https://example.com\x1b\\"]);
+        assert_eq!(
+            CellStyle::default().transition(&linked),
+            "\x1b]8;;https://example.com\x1b\\"
+        );
+        assert_eq!(linked.transition(&CellStyle::default()), "\x1b]8;;\x1b\\");
+    }
+
+    #[test]
+    fn optimize_sgr_drops_redundant_sequences() {
+        assert_eq!(
+            optimize_sgr("\x1b[1ma\x1b[0m\x1b[1mb\x1b[0m"),
+            "\x1b[1mab\x1b[0m"
+        );
+        assert_eq!(optimize_sgr("\x1b[31m\x1b[0m\x1b[0mx\x1b[0m"), "x");
+        assert_eq!(
+            optimize_sgr("\x1b[1;31ma\x1b[0m\x1b[31mb\x1b[0m"),
+            "\x1b[1;31ma\x1b[0;31mb\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn optimize_sgr_keeps_other_escapes() {
+        assert_eq!(
+            optimize_sgr("\x1b[1m\x1b[2Ka\x1b[0m\x1b[0m"),
+            "\x1b[1m\x1b[2Ka\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn unknown_escapes_are_kept_with_the_cells() {
+        let buffer = Buffer::from_ansi("\x1b]0;t\x07a\x1b[2Kb");
+        assert_eq!(buffer.lines[0][0].symbol, "\x1b]0;t\x07a\x1b[2K");
+        assert_eq!(buffer.to_ansi(), "\x1b]0;t\x07a\x1b[2Kb");
+    }
+}
@@ -0,0 +1,84 @@
+use std::cmp;
+
+use crate::buffer::Buffer;
+
+pub struct Layer {
+    pub content: String,
+    pub x: usize,
+    pub y: usize,
+    pub z: i32,
+}
+
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub layers: Vec<Layer>,
+}
+
+impl Layer {
+    pub fn new(content: String) -> Self {
+        Self {
+            content,
+            x: 0,
+            y: 0,
+            z: 0,
+        }
+    }
+
+    pub fn x(mut self, value: usize) -> Self {
+        self.x = value;
+        self
+    }
+
+    pub fn y(mut self, value: usize) -> Self {
+        self.y = value;
+        self
+    }
+
+    pub fn z(mut self, value: i32) -> Self {
+        self.z = value;
+        self
+    }
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            layers: vec![],
+        }
+    }
+
+    pub fn layer(mut self, l: Layer) -> Self {
+        self.layers.push(l);
+        self
+    }
+
+    // Layers are drawn from the lowest to the highest z. Layers with the same z are drawn in the
+    // order they were added. Anything past the edges of the canvas is cut off.
+    pub fn render(&self) -> String {
+        let mut compiled = Buffer::new(self.width, self.height);
+
+        let mut layers: Vec<&Layer> = self.layers.iter().collect();
+        layers.sort_by_key(|l| l.z);
+        for l in layers {
+            let mut top = Buffer::from_ansi(&l.content);
+            top.pad_lines();
+            compiled.blit(&top, l.x, l.y);
+        }
+        compiled.to_ansi()
+    }
+}
+
+// Draws `top` over `base` with its top left corner at column `x` and line `y`. The parts of `base`
+// which are not covered keep their styling, lines of `top` which fall below `base` are dropped.
+pub fn overlay(base: &str, top: &str, x: usize, y: usize) -> String {
+    let mut compiled = Buffer::from_ansi(base);
+    let mut top = Buffer::from_ansi(top);
+    top.pad_lines();
+
+    compiled.width = cmp::max(compiled.width, x + top.width);
+    compiled.blit(&top, x, y);
+    compiled.to_ansi()
+}
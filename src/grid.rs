@@ -0,0 +1,354 @@
+use std::cmp;
+
+use crate::{
+    align::{get_lines, get_strs_height},
+    measure,
+    position::{place, Position},
+    style::{Props, Style},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Track {
+    Fixed(usize),
+    Fraction(usize),
+    Auto,
+}
+
+#[derive(Clone)]
+pub struct GridCell {
+    pub content: String,
+    pub style: Style,
+    pub row: usize,
+    pub column: usize,
+    pub row_span: usize,
+    pub column_span: usize,
+    pub h_pos: Position,
+    pub v_pos: Position,
+}
+
+#[derive(Clone)]
+pub struct Grid {
+    pub columns: Vec<Track>,
+    pub rows: Vec<Track>,
+    pub cells: Vec<GridCell>,
+    pub row_gap: usize,
+    pub column_gap: usize,
+}
+
+impl GridCell {
+    pub fn new(row: usize, column: usize, content: String) -> Self {
+        Self {
+            content,
+            style: Style::new_style(),
+            row,
+            column,
+            row_span: 1,
+            column_span: 1,
+            h_pos: Position::Left,
+            v_pos: Position::Top,
+        }
+    }
+
+    pub fn span(mut self, rows: usize, columns: usize) -> Self {
+        self.row_span = cmp::max(1, rows);
+        self.column_span = cmp::max(1, columns);
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn align(mut self, h_pos: Position, v_pos: Position) -> Self {
+        self.h_pos = h_pos;
+        self.v_pos = v_pos;
+        self
+    }
+
+    // The style without its own size, the cell decides the size of the block.
+    fn frame_style(&self) -> Style {
+        let mut style = self.style.copy();
+        for key in [
+            Props::WidthKey,
+            Props::HeightKey,
+            Props::MaxWidthKey,
+            Props::MaxHeightKey,
+            Props::MinWidthKey,
+            Props::MinHeightKey,
+        ] {
+            style.rules.remove(&key);
+        }
+        style
+    }
+
+    fn padding_size(&self) -> (usize, usize) {
+        (
+            self.style.get_as_int(Props::PaddingLeftKey)
+                + self.style.get_as_int(Props::PaddingRightKey),
+            self.style.get_as_int(Props::PaddingTopKey)
+                + self.style.get_as_int(Props::PaddingBottomKey),
+        )
+    }
+
+    fn natural_width(&self) -> usize {
+        let (_, width) = get_lines(&self.frame_style().render(self.content.to_string()));
+        width
+    }
+
+    fn natural_height(&self, width: usize) -> usize {
+        let (padding_w, padding_h) = self.padding_size();
+        let content_width = width
+            .saturating_sub(self.style.get_horizontal_frame_size())
+            .saturating_sub(padding_w);
+        let wrapped = measure::fill(&self.content, cmp::max(1, content_width));
+        get_strs_height(&wrapped) + padding_h + self.style.get_vertical_frame_size()
+    }
+
+    // Renders the cell so that the decorated block is exactly `width` x `height` cells.
+    fn render(&self, width: usize, height: usize) -> String {
+        if width == 0 || height == 0 {
+            return vec![" ".repeat(width); height].join("\n");
+        }
+        let (padding_w, padding_h) = self.padding_size();
+        let content_width = width
+            .saturating_sub(self.style.get_horizontal_frame_size())
+            .saturating_sub(padding_w);
+        let content_height = height
+            .saturating_sub(self.style.get_vertical_frame_size())
+            .saturating_sub(padding_h);
+
+        let wrapped = measure::fill(&self.content, cmp::max(1, content_width));
+        let placed = place(
+            content_width as i32,
+            content_height as i32,
+            self.h_pos,
+            self.v_pos,
+            wrapped,
+            &[],
+        );
+        let rendered = self
+            .frame_style()
+            .max_width(width as i32)
+            .max_height(height as i32)
+            .render(placed);
+        place(
+            width as i32,
+            height as i32,
+            Position::Left,
+            Position::Top,
+            rendered,
+            &[],
+        )
+    }
+}
+
+impl Grid {
+    pub fn new(columns: &[Track], rows: &[Track]) -> Self {
+        Self {
+            columns: columns.to_vec(),
+            rows: rows.to_vec(),
+            cells: vec![],
+            row_gap: 0,
+            column_gap: 0,
+        }
+    }
+
+    pub fn gap(mut self, row_gap: usize, column_gap: usize) -> Self {
+        self.row_gap = row_gap;
+        self.column_gap = column_gap;
+        self
+    }
+
+    pub fn cell(mut self, c: GridCell) -> Self {
+        self.cells.push(c);
+        self
+    }
+
+    fn column_widths(&self, width: usize) -> Vec<usize> {
+        resolve_tracks(&self.columns, width, self.column_gap, |i| {
+            self.cells
+                .iter()
+                .filter(|c| c.column == i && c.column_span == 1)
+                .map(|c| c.natural_width())
+                .max()
+                .unwrap_or(0)
+        })
+    }
+
+    fn row_heights(&self, height: usize, widths: &[usize]) -> Vec<usize> {
+        resolve_tracks(&self.rows, height, self.row_gap, |i| {
+            self.cells
+                .iter()
+                .filter(|c| c.row == i && c.row_span == 1)
+                .map(|c| {
+                    c.natural_height(span_size(widths, c.column, c.column_span, self.column_gap))
+                })
+                .max()
+                .unwrap_or(0)
+        })
+    }
+
+    pub fn render(&self, width: usize, height: usize) -> String {
+        let widths = self.column_widths(width);
+        let heights = self.row_heights(height, &widths);
+
+        let mut row_offsets = vec![0; heights.len()];
+        let mut total_height = 0;
+        for (i, h) in heights.iter().enumerate() {
+            row_offsets[i] = total_height;
+            total_height += h;
+            if i < heights.len() - 1 {
+                total_height += self.row_gap;
+            }
+        }
+
+        // Cells which start outside of the grid are dropped and spans are cut at the grid edges.
+        let mut blocks = vec![];
+        for c in &self.cells {
+            if c.row >= heights.len() || c.column >= widths.len() {
+                continue;
+            }
+            let row_span = cmp::min(c.row_span, heights.len() - c.row);
+            let column_span = cmp::min(c.column_span, widths.len() - c.column);
+            let w = span_size(&widths, c.column, column_span, self.column_gap);
+            let h = span_size(&heights, c.row, row_span, self.row_gap);
+            let lines: Vec<String> = c.render(w, h).split('\n').map(|l| l.to_string()).collect();
+            blocks.push((c.column, c.column + column_span, row_offsets[c.row], lines));
+        }
+
+        let mut compiled_string = String::new();
+        for y in 0..total_height {
+            let mut column = 0;
+            while column < widths.len() {
+                let block = blocks.iter().find(|(start, end, top, lines)| {
+                    *start <= column && column < *end && *top <= y && y < top + lines.len()
+                });
+                match block {
+                    Some((start, end, top, lines)) if *start == column => {
+                        compiled_string.push_str(&lines[y - top]);
+                        column = *end;
+                    }
+                    // Covered by a block which started in an earlier column.
+                    Some((_, end, _, _)) => column = *end,
+                    None => {
+                        compiled_string.push_str(&" ".repeat(widths[column]));
+                        column += 1;
+                    }
+                }
+                if column < widths.len() {
+                    compiled_string.push_str(&" ".repeat(self.column_gap));
+                }
+            }
+            if y < total_height - 1 {
+                compiled_string.push('\n');
+            }
+        }
+        compiled_string
+    }
+}
+
+fn span_size(sizes: &[usize], start: usize, span: usize, gap: usize) -> usize {
+    let end = cmp::min(start + span, sizes.len());
+    if start >= end {
+        return 0;
+    }
+    sizes[start..end].iter().sum::<usize>() + gap * (end - start - 1)
+}
+
+// Fixed tracks take their size, auto tracks take the size of their content and the fractions
+// share whatever is left.
+fn resolve_tracks<F>(tracks: &[Track], available: usize, gap: usize, content_size: F) -> Vec<usize>
+where
+    F: Fn(usize) -> usize,
+{
+    let mut sizes: Vec<usize> = tracks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| match t {
+            Track::Fixed(n) => *n,
+            Track::Auto => content_size(i),
+            Track::Fraction(_) => 0,
+        })
+        .collect();
+
+    let used = sizes.iter().sum::<usize>() + gap * tracks.len().saturating_sub(1);
+    let free = available.saturating_sub(used);
+    let total_fr: usize = tracks
+        .iter()
+        .map(|t| match t {
+            Track::Fraction(n) => *n,
+            _ => 0,
+        })
+        .sum();
+    if total_fr == 0 {
+        return sizes;
+    }
+
+    let mut remainder = free;
+    for (i, t) in tracks.iter().enumerate() {
+        if let Track::Fraction(n) = t {
+            sizes[i] = free * n / total_fr;
+            remainder -= sizes[i];
+        }
+    }
+    for (i, t) in tracks.iter().enumerate() {
+        if remainder == 0 {
+            break;
+        }
+        if let Track::Fraction(n) = t {
+            if *n > 0 {
+                sizes[i] += 1;
+                remainder -= 1;
+            }
+        }
+    }
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractions_share_the_space_left_by_fixed_and_auto_tracks() {
+        let tracks = [
+            Track::Fixed(4),
+            Track::Auto,
+            Track::Fraction(1),
+            Track::Fraction(2),
+        ];
+        // The rounding remainder goes to the first fraction.
+        assert_eq!(resolve_tracks(&tracks, 20, 1, |_| 3), [4, 3, 4, 6]);
+    }
+
+    #[test]
+    fn fractions_get_nothing_when_the_space_is_used_up() {
+        let tracks = [Track::Fixed(4), Track::Auto, Track::Fraction(1)];
+        assert_eq!(resolve_tracks(&tracks, 5, 1, |_| 3), [4, 3, 0]);
+    }
+
+    #[test]
+    fn auto_tracks_take_the_widest_cell() {
+        let grid = Grid::new(&[Track::Auto, Track::Fraction(1)], &[Track::Auto])
+            .cell(GridCell::new(0, 0, "abc".to_string()))
+            .cell(GridCell::new(0, 0, "abcde".to_string()));
+        assert_eq!(grid.column_widths(10), [5, 5]);
+    }
+
+    #[test]
+    fn spans_include_the_gaps() {
+        assert_eq!(span_size(&[3, 4, 5], 0, 2, 1), 8);
+        assert_eq!(span_size(&[3, 4, 5], 1, 5, 1), 10);
+        assert_eq!(span_size(&[3, 4, 5], 3, 1, 1), 0);
+    }
+
+    #[test]
+    fn render_fills_the_grid() {
+        let grid = Grid::new(&[Track::Fixed(3), Track::Fraction(1)], &[Track::Fixed(2)])
+            .gap(0, 1)
+            .cell(GridCell::new(0, 1, "ab".to_string()));
+        let rendered = grid.render(8, 2);
+        assert_eq!(measure::strip_ansi(&rendered), "    ab  \n        ");
+    }
+}
@@ -1,120 +1,248 @@
-use textwrap::core::display_width;
+use std::{cmp, collections::HashMap};
+
+use crate::{
+    border::{merge_junction, render_horizontal_edge},
+    buffer::{Buffer, Cell},
+    position::Position,
+    whitespace::{WhiteSpace, WhiteSpaceType},
+};
+
+#[derive(Clone)]
+pub enum JoinType {
+    Gap(usize),
+    Separator(String),
+    Fill(WhiteSpaceType),
+    Align(usize, Position),
+    Collapse,
+}
 
-use crate::{align::get_lines, position::Position};
+// Blank columns, or lines when joining vertically, between the blocks. With a separator the gap
+// is applied twice, once on each side of it.
+pub fn with_gap(n: usize) -> JoinType {
+    JoinType::Gap(n)
+}
 
-pub fn join_horizontally(pos: Position, strs: &[String]) -> String {
-    let mut compiled_string = String::new();
+// Drawn between every pair of blocks, on every line when joining horizontally and as a rule across
+// the widest block when joining vertically. Only the first line of a multi-line separator is used.
+pub fn with_separator(strs: String) -> JoinType {
+    JoinType::Separator(strs)
+}
 
-    if strs.len() == 0 {
-        return compiled_string;
-    }
+pub fn with_fill(ws: WhiteSpaceType) -> JoinType {
+    JoinType::Fill(ws)
+}
 
-    if strs.len() == 1 {
-        return strs[0].to_string();
-    }
+// Overrides the alignment of the block at the given index. Only used when joining vertically.
+pub fn with_block_align(index: usize, pos: Position) -> JoinType {
+    JoinType::Align(index, pos)
+}
 
-    let mut blocks = vec![vec![String::new()]; strs.len()];
-    let mut max_widths: Vec<usize> = vec![0; strs.len()];
-    let mut max_height = 0;
+// Draws the blocks one column or line over each other, so that the borders next to each other
+// become a single shared border with junctions where they meet. The gap and the separator are not
+// used with it.
+pub fn with_collapsed_borders() -> JoinType {
+    JoinType::Collapse
+}
 
-    for (i, str) in strs.iter().enumerate() {
-        let (lines, width) = get_lines(str);
-        max_widths[i] = width;
-        blocks[i] = lines.map(|s| s.to_string()).collect();
-        if blocks[i].len() > max_height {
-            max_height = blocks[i].len();
-        }
-    }
+struct JoinOptions {
+    gap: usize,
+    separator: String,
+    fill: WhiteSpace,
+    aligns: HashMap<usize, Position>,
+    collapse: bool,
+}
 
-    for (i, block) in blocks.clone().iter().enumerate() {
-        if block.len() > max_height {
-            continue;
-        }
-        let extra_lines = vec![String::new(); max_height - block.len()];
-        match pos {
-            Position::Top => {
-                blocks[i].extend(extra_lines);
-            }
-            Position::Bottom => {
-                // cloned returns the owned values. The rev returns the references after reversing.
-                blocks[i].extend(extra_lines.iter().cloned().rev());
-            }
-            Position::Center | _ => {
-                // Split in the middle and add it to the top and bottom.
-                let (mut top, mut bottom) = (
-                    vec![String::new(); (max_height - block.len()) / 2],
-                    vec![String::new(); (max_height - block.len()) / 2],
-                );
-                top = extra_lines.clone()[0..top.len()].to_vec();
-                bottom = extra_lines.clone()[bottom.len()..].to_vec();
-                blocks[i].extend(top);
-                blocks[i].extend(bottom.iter().cloned().rev());
+impl JoinOptions {
+    fn new(opts: &[JoinType]) -> Self {
+        let mut gap = 0;
+        let mut separator = String::new();
+        let mut fill = vec![];
+        let mut aligns = HashMap::new();
+        let mut collapse = false;
+        for o in opts {
+            match o {
+                JoinType::Gap(n) => gap = *n,
+                JoinType::Separator(val) => {
+                    separator = val.split('\n').next().unwrap_or("").to_string()
+                }
+                JoinType::Fill(val) => fill.push(val.clone()),
+                JoinType::Align(i, pos) => {
+                    aligns.insert(*i, *pos);
+                }
+                JoinType::Collapse => collapse = true,
             }
         }
-    }
-
-    // Iterate through the lines and combine each line from the block and make it a single line to display it
-    // horizontally.
-    for i in 0..blocks[0].len() {
-        for block in &blocks {
-            compiled_string.push_str(&block[i]);
-        }
-        if i < blocks[0].len() - 1 {
-            compiled_string.push_str("\n");
+        Self {
+            gap,
+            separator,
+            fill: WhiteSpace::new(&fill),
+            aligns,
+            collapse,
         }
     }
-
-    compiled_string
 }
 
-pub fn join_vertically(pos: Position, strs: &[String]) -> String {
-    let mut compiled_string = String::new();
-    if strs.len() == 0 {
-        return compiled_string;
-    }
+pub fn join_horizontally(pos: Position, strs: &[String]) -> String {
+    join_horizontally_with(pos, strs, &[])
+}
 
-    if strs.len() == 1 {
-        return strs[0].to_string();
+pub fn join_horizontally_with(pos: Position, strs: &[String], opts: &[JoinType]) -> String {
+    if strs.is_empty() {
+        return String::new();
     }
 
-    let mut blocks = vec![vec![String::new()]; strs.len()];
-    let mut max_width = 0;
+    let mut options = JoinOptions::new(opts);
+    let blocks: Vec<Buffer> = strs.iter().map(|s| Buffer::from_ansi(s)).collect();
+    let max_height = blocks.iter().map(|b| b.height()).max().unwrap_or(0);
 
-    for (i, line) in strs.iter().enumerate() {
-        let (lines, width) = get_lines(line);
-        blocks[i] = lines.map(|s| s.to_string()).collect();
-        if width > max_width {
-            max_width = width;
-        }
+    // The separator sits in the middle of the gap when both of them are set, so the divider is the
+    // gap twice plus the separator.
+    let separator = Buffer::from_ansi(&options.separator);
+    let mut divider_width = options.gap;
+    if !options.separator.is_empty() {
+        divider_width = 2 * options.gap + separator.width;
     }
+    let mut overlap = 0;
+    if options.collapse {
+        divider_width = 0;
+        overlap = 1;
+    }
+    let width = (blocks.iter().map(|b| b.width).sum::<usize>()
+        + divider_width * (blocks.len() - 1))
+        .saturating_sub(overlap * (blocks.len() - 1));
+
+    // Every block is drawn over the fill, so ragged blocks get padded to their own width and
+    // don't push the blocks to their right out of alignment.
+    let mut compiled = Buffer::new(width, max_height);
+    compiled.fill(&mut options.fill);
+
+    let mut x = 0;
+    let mut previous = 0..0;
+    for (i, block) in blocks.iter().enumerate() {
+        let extra = max_height - block.height();
+        let y = match pos {
+            Position::Bottom => extra,
+            Position::Center => extra / 2,
+            _ => 0,
+        };
+        let lines = y..y + block.height();
+        let shared: Vec<(usize, usize)> = match options.collapse && i > 0 {
+            true => (cmp::max(lines.start, previous.start)..cmp::min(lines.end, previous.end))
+                .map(|line| (x, line))
+                .collect(),
+            false => vec![],
+        };
+        let under = saved_cells(&compiled, &shared);
+        compiled.blit(block, x, y);
+        merge_junctions(&mut compiled, under);
+        x += block.width.saturating_sub(overlap);
+        previous = lines;
 
-    for (i, block) in blocks.clone().iter().enumerate() {
-        for (j, line) in block.iter().enumerate() {
-            let w = max_width - display_width(line);
-            match pos {
-                Position::Left => {
-                    compiled_string.push_str(&line);
-                    compiled_string.push_str(&" ".repeat(w));
-                }
-                Position::Right => {
-                    compiled_string.push_str(&" ".repeat(w));
-                    compiled_string.push_str(&line);
-                }
-                Position::Center | _ => {
-                    compiled_string.push_str(&" ".repeat(w / 2));
-                    compiled_string.push_str(&line);
-                    compiled_string.push_str(&" ".repeat(w / 2));
+        if i < blocks.len() - 1 {
+            if !options.separator.is_empty() && !options.collapse {
+                for line in 0..max_height {
+                    compiled.blit_line(&separator.lines[0], x + options.gap, line);
                 }
             }
+            x += divider_width;
+        }
+    }
+
+    compiled.to_ansi()
+}
+
+pub fn join_vertically(pos: Position, strs: &[String]) -> String {
+    join_vertically_with(pos, strs, &[])
+}
+
+pub fn join_vertically_with(pos: Position, strs: &[String], opts: &[JoinType]) -> String {
+    if strs.is_empty() {
+        return String::new();
+    }
 
-            if j < block.len() - 1 {
-                compiled_string.push_str("\n");
+    let mut options = JoinOptions::new(opts);
+    let blocks: Vec<Buffer> = strs.iter().map(|s| Buffer::from_ansi(s)).collect();
+    let max_width = blocks.iter().map(|b| b.width).max().unwrap_or(0);
+
+    // The gap is made of blank lines and the separator is repeated as a rule across the widest block,
+    // with the gap above and below it.
+    let mut divider_height = options.gap;
+    if !options.separator.is_empty() {
+        divider_height = 2 * options.gap + 1;
+    }
+    let mut overlap = 0;
+    if options.collapse {
+        divider_height = 0;
+        overlap = 1;
+    }
+    let rule = Buffer::from_ansi(&render_horizontal_edge(
+        "",
+        &options.separator,
+        "",
+        max_width,
+    ));
+    let height = (blocks.iter().map(|b| b.height()).sum::<usize>()
+        + divider_height * (blocks.len() - 1))
+        .saturating_sub(overlap * (blocks.len() - 1));
+
+    let mut compiled = Buffer::new(max_width, height);
+    compiled.fill(&mut options.fill);
+
+    let mut y = 0;
+    let mut previous = 0..0;
+    for (i, block) in blocks.iter().enumerate() {
+        let block_pos = options.aligns.get(&i).copied().unwrap_or(pos);
+        let mut under = vec![];
+        for (j, line) in block.lines.iter().enumerate() {
+            let w = max_width - line.len();
+            let x = match block_pos {
+                Position::Left => 0,
+                Position::Right => w,
+                _ => w / 2,
+            };
+            let columns = x..x + line.len();
+            if options.collapse && i > 0 && j == 0 {
+                let shared: Vec<(usize, usize)> = (cmp::max(columns.start, previous.start)
+                    ..cmp::min(columns.end, previous.end))
+                    .map(|column| (column, y))
+                    .collect();
+                under = saved_cells(&compiled, &shared);
             }
+            compiled.blit_line(line, x, y);
+            previous = columns;
+            y += 1;
         }
+        merge_junctions(&mut compiled, under);
+        y -= cmp::min(overlap, y);
+
         if i < blocks.len() - 1 {
-            compiled_string.push_str("\n");
+            if !options.separator.is_empty() && !options.collapse {
+                compiled.blit(&rule, 0, y + options.gap);
+            }
+            y += divider_height;
         }
     }
 
-    compiled_string
+    compiled.to_ansi()
+}
+
+fn saved_cells(compiled: &Buffer, positions: &[(usize, usize)]) -> Vec<(usize, usize, Cell)> {
+    positions
+        .iter()
+        .filter_map(|(x, y)| {
+            let cell = compiled.lines.get(*y)?.get(*x)?;
+            Some((*x, *y, cell.clone()))
+        })
+        .collect()
+}
+
+// Turns the border chars which were drawn over the saved ones into the junctions of both.
+fn merge_junctions(compiled: &mut Buffer, under: Vec<(usize, usize, Cell)>) {
+    for (x, y, cell) in under {
+        if let Some(top) = compiled.lines.get_mut(y).and_then(|l| l.get_mut(x)) {
+            if let Some(symbol) = merge_junction(&cell.symbol, &top.symbol) {
+                top.symbol = symbol;
+            }
+        }
+    }
 }
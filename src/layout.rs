@@ -0,0 +1,358 @@
+use std::cmp;
+
+use crate::{
+    align::{get_lines, get_strs_height},
+    join::{join_horizontally_with, join_vertically_with, with_gap},
+    position::Position,
+    style::Style,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    Row,
+    Column,
+}
+
+#[derive(Clone)]
+pub enum Content {
+    Text(Style, String),
+    Container(Container),
+}
+
+#[derive(Clone)]
+pub struct FlexItem {
+    pub content: Content,
+    pub grow: usize,
+    pub shrink: usize,
+    pub basis: Option<usize>,
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+#[derive(Clone)]
+pub struct Container {
+    pub direction: Direction,
+    pub children: Vec<FlexItem>,
+    pub gap: usize,
+}
+
+impl FlexItem {
+    fn new(content: Content) -> Self {
+        Self {
+            content,
+            grow: 0,
+            shrink: 1,
+            basis: None,
+            min: 0,
+            max: None,
+        }
+    }
+
+    pub fn text(style: Style, strs: String) -> Self {
+        Self::new(Content::Text(style, strs))
+    }
+
+    pub fn container(c: Container) -> Self {
+        Self::new(Content::Container(c))
+    }
+
+    pub fn grow(mut self, value: usize) -> Self {
+        self.grow = value;
+        self
+    }
+
+    pub fn shrink(mut self, value: usize) -> Self {
+        self.shrink = value;
+        self
+    }
+
+    pub fn basis(mut self, value: usize) -> Self {
+        self.basis = Some(value);
+        self
+    }
+
+    pub fn min(mut self, value: usize) -> Self {
+        self.min = value;
+        self
+    }
+
+    pub fn max(mut self, value: usize) -> Self {
+        self.max = Some(value);
+        self
+    }
+
+    fn clamp(&self, size: usize) -> usize {
+        let mut size = cmp::max(size, self.min);
+        if let Some(max) = self.max {
+            size = cmp::min(size, cmp::max(max, self.min));
+        }
+        size
+    }
+
+    // Size of the item when it is not constrained by its parent.
+    fn natural_size(&self) -> (usize, usize) {
+        match &self.content {
+            Content::Text(style, strs) => {
+                let rendered = style.render(strs.to_string());
+                let (_, width) = get_lines(&rendered);
+                (width, get_strs_height(&rendered))
+            }
+            Content::Container(c) => c.natural_size(),
+        }
+    }
+
+    // The natural size is only asked for when there is no basis, so that the content isn't
+    // rendered when it isn't needed or when the caller already has the size.
+    fn main_size<F>(&self, direction: Direction, natural_size: F) -> usize
+    where
+        F: FnOnce() -> (usize, usize),
+    {
+        if let Some(basis) = self.basis {
+            return self.clamp(basis);
+        }
+        let (width, height) = natural_size();
+        match direction {
+            Direction::Row => self.clamp(width),
+            Direction::Column => self.clamp(height),
+        }
+    }
+
+    fn render(&self, width: usize, height: usize) -> String {
+        match &self.content {
+            Content::Text(style, strs) => render_text(style, strs, width, height),
+            Content::Container(c) => c.render(width, height),
+        }
+    }
+}
+
+impl Container {
+    fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            children: vec![],
+            gap: 0,
+        }
+    }
+
+    pub fn row() -> Self {
+        Self::new(Direction::Row)
+    }
+
+    pub fn column() -> Self {
+        Self::new(Direction::Column)
+    }
+
+    pub fn gap(mut self, value: usize) -> Self {
+        self.gap = value;
+        self
+    }
+
+    pub fn child(mut self, item: FlexItem) -> Self {
+        self.children.push(item);
+        self
+    }
+
+    pub fn children(mut self, items: &[FlexItem]) -> Self {
+        self.children.extend(items.iter().cloned());
+        self
+    }
+
+    fn total_gap(&self) -> usize {
+        self.gap * self.children.len().saturating_sub(1)
+    }
+
+    pub fn natural_size(&self) -> (usize, usize) {
+        let mut main = self.total_gap();
+        let mut cross = 0;
+        for child in &self.children {
+            let (width, height) = child.natural_size();
+            main += child.main_size(self.direction, || (width, height));
+            match self.direction {
+                Direction::Row => cross = cmp::max(cross, height),
+                Direction::Column => cross = cmp::max(cross, width),
+            }
+        }
+        match self.direction {
+            Direction::Row => (main, cross),
+            Direction::Column => (cross, main),
+        }
+    }
+
+    // Computes the size of every child along the main axis for the given available space.
+    pub fn compute_sizes(&self, available: usize) -> Vec<usize> {
+        let available = available.saturating_sub(self.total_gap());
+        let mut sizes: Vec<usize> = self
+            .children
+            .iter()
+            .map(|c| c.main_size(self.direction, || c.natural_size()))
+            .collect();
+        let mut frozen = vec![false; sizes.len()];
+
+        // Every pass hands out the free space (or takes away the overflow) proportionally. Items
+        // which hit their min or max are frozen and the remaining space is distributed again.
+        loop {
+            let used: usize = sizes.iter().sum();
+            if used == available {
+                break;
+            }
+
+            let growing = used < available;
+            let weights: Vec<usize> = self
+                .children
+                .iter()
+                .enumerate()
+                .map(|(i, c)| match (frozen[i], growing) {
+                    (true, _) => 0,
+                    (false, true) => c.grow,
+                    (false, false) => c.shrink * sizes[i],
+                })
+                .collect();
+            let total_weight: usize = weights.iter().sum();
+            if total_weight == 0 {
+                break;
+            }
+
+            let space = if growing {
+                available - used
+            } else {
+                used - available
+            };
+            let mut shares: Vec<usize> = weights.iter().map(|w| space * w / total_weight).collect();
+            let mut remainder = space - shares.iter().sum::<usize>();
+            for (i, w) in weights.iter().enumerate() {
+                if remainder == 0 {
+                    break;
+                }
+                if *w > 0 {
+                    shares[i] += 1;
+                    remainder -= 1;
+                }
+            }
+
+            let mut clamped = false;
+            for (i, child) in self.children.iter().enumerate() {
+                if weights[i] == 0 {
+                    continue;
+                }
+                let target = if growing {
+                    sizes[i] + shares[i]
+                } else {
+                    sizes[i].saturating_sub(shares[i])
+                };
+                sizes[i] = child.clamp(target);
+                if sizes[i] != target {
+                    frozen[i] = true;
+                    clamped = true;
+                }
+            }
+            if !clamped {
+                break;
+            }
+        }
+        sizes
+    }
+
+    pub fn render(&self, width: usize, height: usize) -> String {
+        if self.children.is_empty() {
+            return render_empty(width, height);
+        }
+
+        let (main, cross) = match self.direction {
+            Direction::Row => (width, height),
+            Direction::Column => (height, width),
+        };
+        let sizes = self.compute_sizes(main);
+
+        let blocks: Vec<String> = self
+            .children
+            .iter()
+            .zip(sizes)
+            .map(|(child, size)| match self.direction {
+                Direction::Row => child.render(size, cross),
+                Direction::Column => child.render(cross, size),
+            })
+            .collect();
+
+        match self.direction {
+            Direction::Row => join_horizontally_with(Position::Top, &blocks, &[with_gap(self.gap)]),
+            Direction::Column => {
+                join_vertically_with(Position::Left, &blocks, &[with_gap(self.gap)])
+            }
+        }
+    }
+}
+
+fn render_empty(width: usize, height: usize) -> String {
+    vec![" ".repeat(width); height].join("\n")
+}
+
+// Renders the text so that the whole block, including borders and margins, is exactly
+// `width` x `height` cells.
+fn render_text(style: &Style, strs: &str, width: usize, height: usize) -> String {
+    if width == 0 || height == 0 {
+        return render_empty(width, height);
+    }
+    let inner_width = width.saturating_sub(style.get_horizontal_frame_size());
+    let inner_height = height.saturating_sub(style.get_vertical_frame_size());
+    style
+        .copy()
+        .width(cmp::max(1, inner_width) as i32)
+        .height(cmp::max(1, inner_height) as i32)
+        .max_width(width as i32)
+        .max_height(height as i32)
+        .render(strs.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(basis: usize) -> FlexItem {
+        FlexItem::text(Style::new_style(), String::new()).basis(basis)
+    }
+
+    #[test]
+    fn free_space_is_shared_by_grow_factor() {
+        let row = Container::row().children(&[item(2).grow(1), item(2).grow(3)]);
+        assert_eq!(row.compute_sizes(12), [4, 8]);
+    }
+
+    #[test]
+    fn overflow_is_taken_by_shrink_factor_and_size() {
+        let row = Container::row().children(&[item(12).shrink(1), item(4).shrink(1)]);
+        assert_eq!(row.compute_sizes(12), [9, 3]);
+    }
+
+    #[test]
+    fn clamped_items_leave_the_rest_to_the_others() {
+        let row = Container::row().children(&[item(0).grow(1).max(3), item(0).grow(1)]);
+        assert_eq!(row.compute_sizes(10), [3, 7]);
+
+        let row = Container::row().children(&[item(6).min(5), item(6)]);
+        assert_eq!(row.compute_sizes(8), [5, 3]);
+    }
+
+    #[test]
+    fn gaps_are_taken_out_first() {
+        let row = Container::row()
+            .gap(2)
+            .children(&[item(0).grow(1), item(0).grow(1)]);
+        assert_eq!(row.compute_sizes(10), [4, 4]);
+    }
+
+    #[test]
+    fn natural_size_follows_the_direction() {
+        let children = [
+            FlexItem::text(Style::new_style(), "abc".to_string()),
+            FlexItem::text(Style::new_style(), "de\nf".to_string()),
+        ];
+        assert_eq!(
+            Container::row().gap(1).children(&children).natural_size(),
+            (6, 2)
+        );
+        assert_eq!(
+            Container::column().children(&children).natural_size(),
+            (3, 3)
+        );
+    }
+}
@@ -1,22 +1,45 @@
+mod ansi;
+
 pub mod align;
 pub mod border;
 pub mod color;
+pub mod measure;
 pub mod padding;
 pub mod renderer;
 pub mod style;
+pub mod transform;
 pub use color::Hue;
 
-pub use border::Border;
 pub use border::{
-    block_border, double_border, hidden_border, inner_half_block_border, normal_border,
+    ascii_border, block_border, dashed_border, dotted_border, double_border, double_single_border,
+    hidden_border, inner_half_block_border, markdown_border, normal_border,
     outer_half_block_border, rounded_border, thick_border,
 };
+pub use border::{Border, ParseBorderError};
+
+pub mod buffer;
+pub use buffer::{optimize_sgr, Buffer};
+
+pub mod canvas;
+pub use canvas::{overlay, Canvas, Layer};
+
+pub mod grid;
+pub use grid::{Grid, GridCell, Track};
+
+pub mod layout;
+pub use layout::{Container, Direction, FlexItem};
 
 pub mod join;
-pub use join::{join_horizontally, join_vertically};
+pub use join::{
+    join_horizontally, join_horizontally_with, join_vertically, join_vertically_with,
+    with_block_align, with_collapsed_borders, with_fill, with_gap, with_separator,
+};
+
+pub mod screen;
+pub use screen::Screen;
 
 pub mod position;
 pub use position::{place, place_horizontal, place_vertical, Position};
 
 pub mod whitespace;
-pub use whitespace::{with_whitespace_bg, with_whitespace_chars, with_whitespace_fg};
+pub use whitespace::{expand_tabs, with_whitespace_bg, with_whitespace_chars, with_whitespace_fg};
@@ -0,0 +1,230 @@
+//! Measuring rendered strings.
+//!
+//! Every function here skips ANSI escape sequences: CSI sequences (SGR styling, cursor movement,
+//! erasing, ...), OSC sequences (hyperlinks, window titles, ...) terminated by BEL or ST, and the
+//! two byte escapes. What is left is measured by grapheme clusters, so an emoji ZWJ sequence or a
+//! letter with combining accents counts as a single unit.
+
+use std::cmp;
+
+use textwrap::{
+    core::{Fragment, Word},
+    wrap_algorithms::{wrap_optimal_fit, Penalties},
+    Options, WrapAlgorithm,
+};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    ansi::{tokenize, Token},
+    renderer::ambiguous_width,
+};
+
+/// Returns the number of columns taken up by the widest line.
+pub fn width(strs: &str) -> usize {
+    strs.split('\n').map(line_width).max().unwrap_or(0)
+}
+
+/// Returns the number of lines. An empty string is a single empty line.
+pub fn height(strs: &str) -> usize {
+    strs.split('\n').count()
+}
+
+/// Returns the width and the height of the string.
+pub fn size(strs: &str) -> (usize, usize) {
+    (width(strs), height(strs))
+}
+
+/// Returns the number of columns taken up by a single grapheme cluster.
+///
+/// A cluster is as wide as the widest char in it. Emoji presentation (`U+FE0F`) and pairs of
+/// regional indicators, which make up flags, are two columns wide. Chars with an ambiguous East
+/// Asian width count as one or two columns depending on [`set_ambiguous_width`].
+///
+/// [`set_ambiguous_width`]: crate::renderer::set_ambiguous_width
+pub fn grapheme_width(grapheme: &str) -> usize {
+    let wide = ambiguous_width() == 2;
+    let mut w = 0;
+    let mut regional_indicators = 0;
+    for ch in grapheme.chars() {
+        if ch == '\u{FE0F}' {
+            return 2;
+        }
+        if ('\u{1F1E6}'..='\u{1F1FF}').contains(&ch) {
+            regional_indicators += 1;
+        }
+        let ch_width = match wide {
+            true => UnicodeWidthChar::width_cjk(ch),
+            false => UnicodeWidthChar::width(ch),
+        };
+        w = cmp::max(w, ch_width.unwrap_or(0));
+    }
+    if regional_indicators >= 2 {
+        return 2;
+    }
+    w
+}
+
+/// Returns the string with all the escape sequences removed.
+pub fn strip_ansi(strs: &str) -> String {
+    tokenize(strs)
+        .into_iter()
+        .filter_map(|t| match t {
+            Token::Char(c, _) => Some(c),
+            Token::Escape(_) => None,
+        })
+        .collect()
+}
+
+fn line_width(line: &str) -> usize {
+    tokenize(line)
+        .into_iter()
+        .map(|t| match t {
+            Token::Char(_, w) => w,
+            Token::Escape(_) => 0,
+        })
+        .sum()
+}
+
+// Wraps the text at `width` columns. textwrap measures chars one by one, so the lines are laid out
+// with the grapheme widths from here instead, and the words which are too long for a line are
+// broken between graphemes afterwards.
+pub(crate) fn fill(strs: &str, width: usize) -> String {
+    let options = Options::new(width)
+        .break_words(false)
+        .wrap_algorithm(WrapAlgorithm::Custom(wrap_graphemes));
+    textwrap::fill(strs, options)
+        .split('\n')
+        .map(|line| break_line(line, width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Debug)]
+struct Measured {
+    width: f64,
+    whitespace: f64,
+    penalty: f64,
+}
+
+impl Fragment for Measured {
+    fn width(&self) -> f64 {
+        self.width
+    }
+
+    fn whitespace_width(&self) -> f64 {
+        self.whitespace
+    }
+
+    fn penalty_width(&self) -> f64 {
+        self.penalty
+    }
+}
+
+fn wrap_graphemes<'a, 'b>(words: &'b [Word<'a>], line_widths: &'b [usize]) -> Vec<&'b [Word<'a>]> {
+    let fragments: Vec<Measured> = words
+        .iter()
+        .map(|word| Measured {
+            width: line_width(word.word) as f64,
+            whitespace: line_width(word.whitespace) as f64,
+            penalty: line_width(word.penalty) as f64,
+        })
+        .collect();
+    let line_widths: Vec<f64> = line_widths.iter().map(|w| *w as f64).collect();
+    let lines = wrap_optimal_fit(&fragments, &line_widths, &Penalties::new())
+        .unwrap_or_else(|_| wrap_first_fit(&fragments, &line_widths));
+
+    let mut start = 0;
+    lines
+        .iter()
+        .map(|line| {
+            start += line.len();
+            &words[start - line.len()..start]
+        })
+        .collect()
+}
+
+fn wrap_first_fit<'a>(fragments: &'a [Measured], line_widths: &[f64]) -> Vec<&'a [Measured]> {
+    let max_width = line_widths.last().copied().unwrap_or(0.0);
+    let mut lines = vec![];
+    let mut start = 0;
+    let mut current = 0.0;
+    for (i, fragment) in fragments.iter().enumerate() {
+        if current + fragment.width + fragment.penalty > max_width && i > start {
+            lines.push(&fragments[start..i]);
+            start = i;
+            current = 0.0;
+        }
+        current += fragment.width + fragment.whitespace;
+    }
+    lines.push(&fragments[start..]);
+    lines
+}
+
+// Breaks a line which is wider than `width` between graphemes.
+fn break_line(line: &str, width: usize) -> String {
+    if line_width(line) <= width {
+        return line.to_string();
+    }
+    let mut broken = String::new();
+    let mut current = 0;
+    for token in tokenize(line) {
+        match token {
+            Token::Escape(e) => broken.push_str(e),
+            Token::Char(c, w) => {
+                if current + w > width && current > 0 {
+                    broken.push('\n');
+                    current = 0;
+                }
+                broken.push_str(c);
+                current += w;
+            }
+        }
+    }
+    broken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csi_sequences_take_no_room() {
+        assert_eq!(width("\x1b[1;31mab\x1b[0m"), 2);
+        assert_eq!(width("\x1b[2J\x1b[10;5Hab\x1b[K"), 2);
+    }
+
+    #[test]
+    fn hyperlinks_take_no_room() {
+        assert_eq!(width("\x1b]8;;http://x\x07cd\x1b]8;;\x07"), 2);
+        assert_eq!(width("\x1b]8;id=1;http://x\x1b\\cd\x1b]8;;\x1b\\"), 2);
+    }
+
+    #[test]
+    fn graphemes_are_measured_whole() {
+        assert_eq!(width("👩‍💻"), 2);
+        assert_eq!(width("e\u{301}"), 1);
+        assert_eq!(width("🇳🇱"), 2);
+    }
+
+    #[test]
+    fn size_is_the_widest_line_by_the_line_count() {
+        assert_eq!(
+            size("\x1b[2J\x1b[10;5Hab\x1b]8;;http://x\x07cd\x1b]8;;\x07\n👩‍💻"),
+            (4, 2)
+        );
+        assert_eq!(height("a\n\nb"), 3);
+        assert_eq!(size(""), (0, 1));
+    }
+
+    #[test]
+    fn fill_measures_graphemes() {
+        assert_eq!(fill("👩‍💻👩‍💻 ab", 4), "👩‍💻👩‍💻\nab");
+        assert_eq!(fill("\x1b[1mabc\x1b[0m de", 3), "\x1b[1mabc\x1b[0m\nde");
+    }
+
+    #[test]
+    fn fill_breaks_long_words_between_graphemes() {
+        assert_eq!(fill("👩‍💻👩‍💻👩‍💻", 5), "👩‍💻👩‍💻\n👩‍💻");
+        assert_eq!(fill("abcdefg", 3), "abc\ndef\ng");
+    }
+}
@@ -1,51 +1,40 @@
-use crossterm::style::Attribute;
+use crate::{measure, whitespace::WhiteSpace};
 
-pub fn pad_left(strs: &str, n: usize, style: Option<&String>) -> String {
-    let mut sp = " ".repeat(n);
+pub fn pad_left(strs: &str, n: usize, ws: &mut WhiteSpace) -> String {
     if n == 0 {
         return strs.to_string();
     }
-    if let Some(st) = style {
-        sp = format!("{}{}", st, sp);
-    }
-    let mut temp = String::new();
-    let lines = strs.split("\n");
-    for (i, line) in lines.clone().enumerate() {
-        temp.push_str(&format!("{}{}{}", &sp, line, &Attribute::Reset.to_string()));
-        if i != lines.clone().count() - 1 {
-            temp.push_str("\n");
-        }
-    }
-    return temp;
+    let sp = ws.render(n);
+    strs.split('\n')
+        .map(|line| format!("{}{}", sp, line))
+        .collect::<Vec<String>>()
+        .join("\n")
 }
 
-pub fn pad_right(strs: &str, n: usize, style: Option<&String>) -> String {
-    let mut sp = " ".repeat(n);
+pub fn pad_right(strs: &str, n: usize, ws: &mut WhiteSpace) -> String {
     if n == 0 {
         return strs.to_string();
     }
-    if let Some(st) = style {
-        sp = format!("{}{}", st, sp);
-    }
-    let mut temp = String::new();
-    let lines = strs.split("\n");
-    for (i, line) in lines.clone().enumerate() {
-        temp.push_str(&format!("{}{}{}", line, &sp, &Attribute::Reset.to_string()));
-        if i != lines.clone().count() - 1 {
-            temp.push_str("\n");
-        }
-    }
-    return temp;
+    let sp = ws.render(n);
+    strs.split('\n')
+        .map(|line| format!("{}{}", line, sp))
+        .collect::<Vec<String>>()
+        .join("\n")
 }
 
-pub fn pad_top(strs: &str, n: usize) -> String {
-    let mut sp = "\n".repeat(n);
-    sp = format!("{sp}{strs}");
-    sp
+// The rows added above and below are as wide as the widest line, filled with the whitespace.
+pub fn pad_top(strs: &str, n: usize, ws: &mut WhiteSpace) -> String {
+    if n == 0 {
+        return strs.to_string();
+    }
+    let row = ws.render(measure::width(strs));
+    format!("{}{}", format!("{}\n", row).repeat(n), strs)
 }
 
-pub fn pad_bottom(strs: &str, n: usize) -> String {
-    let mut sp = "\n".repeat(n);
-    sp = format!("{strs}{sp}");
-    sp
+pub fn pad_bottom(strs: &str, n: usize, ws: &mut WhiteSpace) -> String {
+    if n == 0 {
+        return strs.to_string();
+    }
+    let row = ws.render(measure::width(strs));
+    format!("{}{}", strs, format!("\n{}", row).repeat(n))
 }
@@ -1,3 +1,16 @@
+use std::{
+    env,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+// Whether the chars with an ambiguous East Asian width, such as `─`, `│` and `…`, take up two
+// columns. Terminals in CJK locales usually draw them that way.
+static AMBIGUOUS_WIDE: AtomicBool = AtomicBool::new(false);
+
+// Whether the terminal supports the `4:x` underline styles and the `58` underline color. It is
+// guessed from the environment until it gets set. 0 means not known yet, 1 no and 2 yes.
+static EXTENDED_UNDERLINES: AtomicU8 = AtomicU8::new(0);
+
 pub struct Renderer {
     pub color: Option<crossterm::style::Color>,
     pub attribute: Option<crossterm::style::Attribute>,
@@ -13,3 +26,49 @@ impl Renderer {
         }
     }
 }
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Sets the width of the ambiguous chars for the whole process, every width measurement in the
+// crate picks it up. Set it once at startup rather than from different threads.
+pub fn set_ambiguous_width(width: usize) {
+    AMBIGUOUS_WIDE.store(width >= 2, Ordering::Relaxed);
+}
+
+pub fn ambiguous_width() -> usize {
+    match AMBIGUOUS_WIDE.load(Ordering::Relaxed) {
+        true => 2,
+        false => 1,
+    }
+}
+
+// Overrides the guess from the environment for the whole process, like `set_ambiguous_width`.
+pub fn set_extended_underlines(supported: bool) {
+    EXTENDED_UNDERLINES.store(if supported { 2 } else { 1 }, Ordering::Relaxed);
+}
+
+pub fn extended_underlines() -> bool {
+    match EXTENDED_UNDERLINES.load(Ordering::Relaxed) {
+        0 => {
+            let supported = detect_extended_underlines();
+            EXTENDED_UNDERLINES.store(if supported { 2 } else { 1 }, Ordering::Relaxed);
+            supported
+        }
+        value => value == 2,
+    }
+}
+
+// kitty, WezTerm, iTerm2 and the VTE based terminals all support them.
+fn detect_extended_underlines() -> bool {
+    let term = env::var("TERM").unwrap_or_default();
+    let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+    term.contains("kitty")
+        || term.contains("wezterm")
+        || matches!(term_program.as_str(), "iTerm.app" | "WezTerm")
+        || env::var("KITTY_WINDOW_ID").is_ok()
+        || env::var("VTE_VERSION").is_ok()
+}
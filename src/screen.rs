@@ -0,0 +1,173 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    queue,
+    style::Print,
+    terminal::{Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use crate::buffer::{line_to_ansi, Buffer, Cell};
+
+// Draws full frames to the terminal, writing only the cells which changed since the last frame.
+pub struct Screen<W: Write> {
+    pub width: usize,
+    pub height: usize,
+    out: W,
+    previous: Option<Buffer>,
+    alternate: bool,
+}
+
+impl<W: Write> Screen<W> {
+    pub fn new(out: W, width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            out,
+            previous: None,
+            alternate: false,
+        }
+    }
+
+    // Switches to the alternate screen and hides the cursor until `leave` is called or the screen
+    // is dropped.
+    pub fn enter(&mut self) -> io::Result<()> {
+        queue!(self.out, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+        self.alternate = true;
+        self.previous = None;
+        self.out.flush()
+    }
+
+    pub fn leave(&mut self) -> io::Result<()> {
+        if !self.alternate {
+            return Ok(());
+        }
+        queue!(self.out, Show, LeaveAlternateScreen)?;
+        self.alternate = false;
+        self.out.flush()
+    }
+
+    // The next frame is drawn in full since the terminal may have reflowed the old one.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.previous = None;
+    }
+
+    pub fn draw(&mut self, frame: &str) -> io::Result<()> {
+        // Blitting clips the frame to the screen, wide graphemes cut at the edge become spaces.
+        let mut next = Buffer::new(self.width, self.height);
+        next.blit(&Buffer::from_ansi(frame), 0, 0);
+
+        match &self.previous {
+            Some(previous) => {
+                for (y, (old, new)) in previous.lines.iter().zip(&next.lines).enumerate() {
+                    for (start, end) in changed_runs(old, new) {
+                        queue!(
+                            self.out,
+                            MoveTo(start as u16, y as u16),
+                            Print(line_to_ansi(&new[start..end]))
+                        )?;
+                    }
+                }
+            }
+            None => {
+                queue!(self.out, Clear(ClearType::All))?;
+                for (y, line) in next.lines.iter().enumerate() {
+                    queue!(self.out, MoveTo(0, y as u16), Print(line_to_ansi(line)))?;
+                }
+            }
+        }
+
+        self.previous = Some(next);
+        self.out.flush()
+    }
+
+    pub fn writer(&self) -> &W {
+        &self.out
+    }
+
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+}
+
+impl<W: Write> Drop for Screen<W> {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
+
+// Ranges of columns which differ between the two lines. A range never starts or ends in the middle
+// of a wide grapheme.
+fn changed_runs(old: &[Cell], new: &[Cell]) -> Vec<(usize, usize)> {
+    let mut runs = vec![];
+    let mut x = 0;
+    while x < new.len() {
+        if old.get(x) == Some(&new[x]) {
+            x += 1;
+            continue;
+        }
+        let mut start = x;
+        while start > 0 && new[start].is_continuation() {
+            start -= 1;
+        }
+        let mut end = x;
+        while end < new.len() && (old.get(end) != Some(&new[end]) || new[end].is_continuation()) {
+            end += 1;
+        }
+        runs.push((start, end));
+        x = end;
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drawn(screen: &mut Screen<Vec<u8>>, frame: &str) -> String {
+        screen.writer_mut().clear();
+        screen.draw(frame).unwrap();
+        String::from_utf8(screen.writer().clone()).unwrap()
+    }
+
+    #[test]
+    fn first_frame_is_drawn_in_full() {
+        let mut screen = Screen::new(vec![], 5, 2);
+        assert_eq!(
+            drawn(&mut screen, "hello\nworld"),
+            "\x1b[2J\x1b[1;1Hhello\x1b[2;1Hworld"
+        );
+    }
+
+    #[test]
+    fn only_changed_cells_are_written() {
+        let mut screen = Screen::new(vec![], 5, 2);
+        drawn(&mut screen, "hello\nworld");
+        assert_eq!(drawn(&mut screen, "hellO\nworld"), "\x1b[1;5HO");
+        assert_eq!(drawn(&mut screen, "hellO\nworld"), "");
+    }
+
+    #[test]
+    fn replaced_wide_graphemes_are_redrawn_whole() {
+        let mut screen = Screen::new(vec![], 4, 1);
+        drawn(&mut screen, "a中b");
+        assert_eq!(drawn(&mut screen, "axyb"), "\x1b[1;2Hxy");
+        assert_eq!(drawn(&mut screen, "a中b"), "\x1b[1;2H中");
+    }
+
+    #[test]
+    fn wide_graphemes_cut_at_the_edge_are_not_drawn() {
+        let mut screen = Screen::new(vec![], 3, 1);
+        assert_eq!(drawn(&mut screen, "ab中"), "\x1b[2J\x1b[1;1Hab ");
+    }
+
+    #[test]
+    fn resize_forces_a_full_redraw() {
+        let mut screen = Screen::new(vec![], 5, 1);
+        drawn(&mut screen, "hello");
+        screen.resize(3, 1);
+        assert_eq!(drawn(&mut screen, "hello"), "\x1b[2J\x1b[1;1Hhel");
+    }
+}
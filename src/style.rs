@@ -1,14 +1,21 @@
-use std::{cmp, collections::HashMap, usize};
+use std::{borrow::Cow, cmp, collections::HashMap, usize};
 
 use crate::{
     align::{align_text_horizontal, align_text_vertical, get_lines},
-    border::{get_first_char_as_string, render_horizontal_edge, Border},
+    ansi::{tokenize, Token},
+    border::{column_width, pad_corner, render_horizontal_edge, Border},
+    buffer::{underline_color_param, Buffer},
     color::{ColorValue, Hue},
+    measure::{self, grapheme_width},
     padding::{pad_bottom, pad_left, pad_right, pad_top},
     position::Position,
+    renderer::extended_underlines,
+    transform,
+    whitespace::{expand_tabs, with_whitespace_bg, WhiteSpace},
 };
 use crossterm::style::{Attribute, SetBackgroundColor, SetForegroundColor};
 use std::fmt::Write as _;
+use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Eq, Hash, PartialEq, Clone)]
 pub enum Props {
@@ -66,8 +73,36 @@ pub enum Props {
     InlineKey,
     MaxWidthKey,
     MaxHeightKey,
+    MinWidthKey,
+    MinHeightKey,
     UnderlineSpacesKey,
     StrikethroughSpacesKey,
+    TabWidthKey,
+    TransformKey,
+    UnderlineStyleKey,
+    UnderlineColorKey,
+    HyperlinkKey,
+    HyperlinkIdKey,
+
+    // Border titles and footers.
+    BorderTitleKey,
+    BorderTitlePositionKey,
+    BorderTitleStyleKey,
+    BorderFooterKey,
+    BorderFooterPositionKey,
+    BorderFooterStyleKey,
+    BorderTitlePaddingKey,
+
+    // Drop shadow.
+    ShadowOffsetXKey,
+    ShadowOffsetYKey,
+    ShadowColorKey,
+
+    // Custom chars for filling the padding and the margins.
+    PaddingCharsKey,
+    MarginCharsKey,
+
+    ResponsiveKey,
 }
 
 #[derive(Clone)]
@@ -78,6 +113,44 @@ pub enum Value {
     Color(Hue),
     Pos(Position),
     Border(Border),
+    Transform(fn(&str) -> String),
+    Underline(UnderlineStyle),
+    Style(Box<Style>),
+    Responsive(Vec<(usize, Style)>),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UnderlineStyle {
+    #[default]
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+impl UnderlineStyle {
+    pub fn sgr(&self) -> &'static str {
+        match self {
+            UnderlineStyle::Single => "4",
+            UnderlineStyle::Double => "4:2",
+            UnderlineStyle::Curly => "4:3",
+            UnderlineStyle::Dotted => "4:4",
+            UnderlineStyle::Dashed => "4:5",
+        }
+    }
+
+    // Parses the sub param of `4:x`, where 0 means no underline.
+    pub fn from_sgr(sub: &str) -> Option<Self> {
+        match sub {
+            "2" => Some(UnderlineStyle::Double),
+            "3" => Some(UnderlineStyle::Curly),
+            "4" => Some(UnderlineStyle::Dotted),
+            "5" => Some(UnderlineStyle::Dashed),
+            "0" => None,
+            _ => Some(UnderlineStyle::Single),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -130,6 +203,13 @@ impl Style {
         0
     }
 
+    pub fn get_as_string(&self, prop: Props) -> String {
+        if let Some(Value::Str(val)) = self.rules.get(&prop) {
+            return val.to_string();
+        }
+        String::new()
+    }
+
     pub fn get_as_position(&self, prop: Props) -> Position {
         if self.rules.contains_key(&prop) {
             if let Value::Pos(val) = self.rules.get(&prop).unwrap() {
@@ -201,6 +281,18 @@ impl Style {
         self
     }
 
+    // The block grows to at least this size, aligned like it would be with `width` and `height`,
+    // but is left to size itself when the content is bigger.
+    pub fn min_width(mut self, val: i32) -> Self {
+        self.set(Props::MinWidthKey, Value::Int(val as usize));
+        self
+    }
+
+    pub fn min_height(mut self, val: i32) -> Self {
+        self.set(Props::MinHeightKey, Value::Int(val as usize));
+        self
+    }
+
     pub fn width(mut self, val: i32) -> Self {
         self.set(Props::WidthKey, Value::Int(val as usize));
         self
@@ -236,11 +328,104 @@ impl Style {
         self
     }
 
+    // Styled and colored underlines need terminal support, see `renderer::set_extended_underlines`.
+    // Other terminals get a plain underline in the text color.
+    pub fn underline_style(mut self, value: UnderlineStyle) -> Self {
+        self.set(Props::UnderlineKey, Value::Bool(true));
+        self.set(Props::UnderlineStyleKey, Value::Underline(value));
+        self
+    }
+
+    pub fn underline_color(mut self, c: Hue) -> Self {
+        self.set(Props::UnderlineColorKey, Value::Color(c));
+        self
+    }
+
+    fn get_underline_sgr(&self) -> String {
+        if !extended_underlines() {
+            return Attribute::Underlined.to_string();
+        }
+        let mut params = vec![];
+        match self.rules.get(&Props::UnderlineStyleKey) {
+            Some(Value::Underline(style)) => params.push(style.sgr().to_string()),
+            _ => params.push(UnderlineStyle::Single.sgr().to_string()),
+        }
+        if let ColorValue::Color(val) = self.get_as_color(Props::UnderlineColorKey).color {
+            params.push(underline_color_param(val));
+        }
+        format!("\x1b[{}m", params.join(";"))
+    }
+
+    // Makes the text a clickable OSC 8 link. The link is opened again on every line, so it
+    // survives wrapping.
+    pub fn hyperlink(mut self, url: &str) -> Self {
+        self.set(Props::HyperlinkKey, Value::Str(url.to_string()));
+        self
+    }
+
+    // Terminals highlight the links with the same id together, like a link split across panes.
+    pub fn hyperlink_id(mut self, id: &str) -> Self {
+        self.set(Props::HyperlinkIdKey, Value::Str(id.to_string()));
+        self
+    }
+
+    fn get_hyperlink(&self) -> Option<(String, String)> {
+        let url = match self.rules.get(&Props::HyperlinkKey) {
+            Some(Value::Str(url)) if !url.is_empty() => url,
+            _ => return None,
+        };
+        let params = match self.rules.get(&Props::HyperlinkIdKey) {
+            Some(Value::Str(id)) if !id.is_empty() => format!("id={}", id),
+            _ => String::new(),
+        };
+        Some((
+            format!("\x1b]8;{};{}\x1b\\", params, url),
+            String::from("\x1b]8;;\x1b\\"),
+        ))
+    }
+
     pub fn underline_spaces(mut self, value: bool) -> Self {
         self.set(Props::UnderlineSpacesKey, Value::Bool(value));
         self
     }
 
+    pub fn strikethrough_spaces(mut self, value: bool) -> Self {
+        self.set(Props::StrikethroughSpacesKey, Value::Bool(value));
+        self
+    }
+
+    // Whether the padding and the alignment spaces get the foreground and background colors.
+    pub fn color_whitespace(mut self, value: bool) -> Self {
+        self.set(Props::ColorWhitespaceKey, Value::Bool(value));
+        self
+    }
+
+    // Renders everything on a single line, skipping the padding, borders and margins.
+    pub fn inline(mut self, value: bool) -> Self {
+        self.set(Props::InlineKey, Value::Bool(value));
+        self
+    }
+
+    // The number of columns between tab stops, tabs are turned into spaces before the text is
+    // wrapped. Defaults to 4, a width of 0 removes the tabs.
+    pub fn tab_width(mut self, value: i32) -> Self {
+        self.set(Props::TabWidthKey, Value::Int(value as usize));
+        self
+    }
+
+    fn get_tab_width(&self) -> usize {
+        match self.rules.get(&Props::TabWidthKey) {
+            Some(Value::Int(val)) => *val,
+            _ => 4,
+        }
+    }
+
+    // Runs the function over the text before it gets wrapped, for example `transform::uppercase`.
+    pub fn transform(mut self, f: fn(&str) -> String) -> Self {
+        self.set(Props::TransformKey, Value::Transform(f));
+        self
+    }
+
     pub fn padding(mut self, values: &[i32]) -> Self {
         if values.len() > 4 {
             panic!("Cannot provide more than 4 values for padding");
@@ -254,6 +439,12 @@ impl Style {
         self
     }
 
+    // Fills the padding with the chars instead of spaces, colored like the rest of the padding.
+    pub fn padding_chars(mut self, chars: &str) -> Self {
+        self.set(Props::PaddingCharsKey, Value::Str(chars.to_string()));
+        self
+    }
+
     pub fn padding_top(mut self, value: i32) -> Self {
         self.set(Props::PaddingTopKey, Value::Int(value as usize));
         self
@@ -337,6 +528,92 @@ impl Style {
         self
     }
 
+    // The title is drawn inside the top edge of the border, cut short when it doesn't fit. Only the
+    // first line of a multi-line title is used.
+    pub fn border_title(mut self, text: &str, pos: Position) -> Self {
+        self.set(Props::BorderTitleKey, Value::Str(text.to_string()));
+        self.set(Props::BorderTitlePositionKey, Value::Pos(pos));
+        self
+    }
+
+    pub fn border_footer(mut self, text: &str, pos: Position) -> Self {
+        self.set(Props::BorderFooterKey, Value::Str(text.to_string()));
+        self.set(Props::BorderFooterPositionKey, Value::Pos(pos));
+        self
+    }
+
+    // Titles and footers take the colors of their edge unless they have a style of their own.
+    pub fn border_title_style(mut self, style: Style) -> Self {
+        self.set(Props::BorderTitleStyleKey, Value::Style(Box::new(style)));
+        self
+    }
+
+    pub fn border_footer_style(mut self, style: Style) -> Self {
+        self.set(Props::BorderFooterStyleKey, Value::Style(Box::new(style)));
+        self
+    }
+
+    // The spaces on either side of the title and the footer. Defaults to 1.
+    pub fn border_title_padding(mut self, value: i32) -> Self {
+        self.set(Props::BorderTitlePaddingKey, Value::Int(value as usize));
+        self
+    }
+
+    // Draws the horizontal edge with the label from `key`, if there is one, placed along it.
+    fn render_labelled_edge(
+        &self,
+        border: (&str, &str, &str),
+        width: usize,
+        colors: (Hue, Hue),
+        keys: (Props, Props, Props),
+    ) -> String {
+        let (left, middle, right) = border;
+        let (fg, bg) = colors;
+        let (text_key, pos_key, style_key) = keys;
+        // The label has to stay on the edge, so only its first line is used.
+        let text = match self.rules.get(&text_key) {
+            Some(Value::Str(text)) => text.split('\n').next().unwrap_or(""),
+            _ => "",
+        };
+        if text.is_empty() {
+            return style_border(&render_horizontal_edge(left, middle, right, width), fg, bg);
+        }
+        let padding = match self.rules.get(&Props::BorderTitlePaddingKey) {
+            Some(Value::Int(val)) => *val,
+            _ => 1,
+        };
+
+        // One edge char is kept on both sides of the label whenever there is room for it.
+        let edge_room = cmp::min(2, width);
+        let text_room = width.saturating_sub(edge_room + 2 * padding);
+        let label = match self.rules.get(&style_key) {
+            Some(Value::Style(style)) => style.render(text.to_string()),
+            _ => style_border(text, fg, bg),
+        };
+        let label = truncated_string(&label, text_room);
+        let label_width = measure::width(&label);
+        if label_width == 0 {
+            return style_border(&render_horizontal_edge(left, middle, right, width), fg, bg);
+        }
+
+        let free = width - label_width - 2 * padding;
+        let before = match self.get_as_position(pos_key) {
+            Position::Left | Position::Top => cmp::min(1, free),
+            Position::Right | Position::Bottom => free - cmp::min(1, free),
+            Position::Center => free / 2,
+        };
+        let spaces = style_border(&" ".repeat(padding), fg, bg);
+        let edge = |n| render_horizontal_edge("", middle, "", n);
+        format!(
+            "{}{}{}{}{}",
+            style_border(&format!("{}{}", left, edge(before)), fg, bg),
+            spaces,
+            label,
+            spaces,
+            style_border(&format!("{}{}", edge(free - before), right), fg, bg),
+        )
+    }
+
     pub fn foreground(mut self, c: Hue) -> Self {
         self.set(Props::ForegroundKey, Value::Color(c));
         self
@@ -367,6 +644,16 @@ impl Style {
         self
     }
 
+    pub fn margin_chars(mut self, chars: &str) -> Self {
+        self.set(Props::MarginCharsKey, Value::Str(chars.to_string()));
+        self
+    }
+
+    pub fn margin_background(mut self, c: Hue) -> Self {
+        self.set(Props::MarginBackgroundKey, Value::Color(c));
+        self
+    }
+
     pub fn margin_top(mut self, value: i32) -> Self {
         self.set(Props::MarginTopKey, Value::Int(value as usize));
         self
@@ -396,139 +683,208 @@ impl Style {
         let right_margin = self.get_as_int(Props::MarginRightKey);
         let left_margin = self.get_as_int(Props::MarginLeftKey);
 
-        let bgc = self.get_as_color(Props::MarginBackgroundKey);
-        let mut style = None;
-        let mut t = String::new();
-        if bgc != Hue::default() {
-            if let ColorValue::Color(val) = bgc.color {
-                t.push_str(&SetBackgroundColor(val).to_string());
-                style = Some(&t);
-            }
+        let mut style = String::new();
+        if let ColorValue::Color(val) = self.get_as_color(Props::MarginBackgroundKey).color {
+            style.push_str(&SetBackgroundColor(val).to_string());
         }
+        let mut ws = WhiteSpace {
+            style,
+            chars: self.get_as_string(Props::MarginCharsKey),
+        };
 
-        compiled_string = pad_left(&compiled_string, left_margin, style);
-        compiled_string = pad_right(&compiled_string, right_margin, style);
+        compiled_string = pad_left(&compiled_string, left_margin, &mut ws);
+        compiled_string = pad_right(&compiled_string, right_margin, &mut ws);
 
         if !inline {
-            let (_, width) = get_lines(&compiled_string);
-            let sp = " ".repeat(width);
-            if top_margin > 0 {
-                let l = format!("{}\n", sp).repeat(top_margin);
-                compiled_string = format!("{}{}", l, compiled_string);
-            }
-            if bottom_margin > 0 {
-                let l = format!("{}\n", sp).repeat(bottom_margin);
-                compiled_string = format!("{}{}", compiled_string, l);
-            }
+            compiled_string = pad_top(&compiled_string, top_margin, &mut ws);
+            compiled_string = pad_bottom(&compiled_string, bottom_margin, &mut ws);
         }
 
         compiled_string.to_string()
     }
 
-    fn apply_border(&self, strs: &str) -> String {
+    fn get_border_sides(&self) -> (bool, bool, bool, bool) {
         let top_set = self.is_set(Props::BorderTopKey);
         let right_set = self.is_set(Props::BorderRightKey);
         let bottom_set = self.is_set(Props::BorderBottomKey);
         let left_set = self.is_set(Props::BorderLeftKey);
 
-        let mut border = self.get_border_style();
-        let mut has_top = self.get_as_bool(Props::BorderTopKey, false);
-        let mut has_right = self.get_as_bool(Props::BorderRightKey, false);
-        let mut has_bottom = self.get_as_bool(Props::BorderBottomKey, false);
-        let mut has_left = self.get_as_bool(Props::BorderLeftKey, false);
+        let border = self.get_border_style();
 
-        let top_fg = self.get_as_color(Props::BorderTopForegroundKey);
-        let bottom_fg = self.get_as_color(Props::BorderBottomForegroundKey);
-        let right_fg = self.get_as_color(Props::BorderRightForegroundKey);
-        let left_fg = self.get_as_color(Props::BorderLeftForegroundKey);
+        // if border is not set or all the sides have been disabled then no border is applied.
+        if border == Border::default() || (!top_set && !right_set && !bottom_set && !left_set) {
+            return (false, false, false, false);
+        }
 
-        let top_bg = self.get_as_color(Props::BorderTopBackgroundKey);
-        let right_bg = self.get_as_color(Props::BorderRightBackgroundKey);
-        let left_bg = self.get_as_color(Props::BorderLeftBackgroundKey);
-        let bottom_bg = self.get_as_color(Props::BorderBottomBackgroundKey);
+        (
+            self.get_as_bool(Props::BorderTopKey, false),
+            self.get_as_bool(Props::BorderRightKey, false),
+            self.get_as_bool(Props::BorderBottomKey, false),
+            self.get_as_bool(Props::BorderLeftKey, false),
+        )
+    }
 
-        // if border is not set or all the sides have been disabled then return the str as it is without applying borders.
-        if border == Border::default() || (!top_set && !right_set && !bottom_set && !left_set) {
-            return strs.to_string();
+    // Width taken up by the left and right borders, the shadow and the margins, i.e. everything
+    // outside of the `width` of the style.
+    pub fn get_horizontal_frame_size(&self) -> usize {
+        let (_, left_width, right_width) = self.get_frame_border();
+        let (shadow_x, _) = self.get_shadow_offset();
+        self.get_as_int(Props::MarginLeftKey)
+            + self.get_as_int(Props::MarginRightKey)
+            + left_width
+            + right_width
+            + shadow_x
+    }
+
+    // Height taken up by the top and bottom borders, the shadow and the margins.
+    pub fn get_vertical_frame_size(&self) -> usize {
+        let (has_top, _, has_bottom, _) = self.get_border_sides();
+        let (_, shadow_y) = self.get_shadow_offset();
+        self.get_as_int(Props::MarginTopKey)
+            + self.get_as_int(Props::MarginBottomKey)
+            + has_top as usize
+            + has_bottom as usize
+            + shadow_y
+    }
+
+    // Casts a shadow of the block, border included, `offset_x` columns to the right and
+    // `offset_y` lines down. The shadow is made of spaces with the color as their background.
+    // Negative offsets are treated as 0.
+    pub fn shadow(mut self, offset_x: i32, offset_y: i32, c: Hue) -> Self {
+        self.set(
+            Props::ShadowOffsetXKey,
+            Value::Int(cmp::max(0, offset_x) as usize),
+        );
+        self.set(
+            Props::ShadowOffsetYKey,
+            Value::Int(cmp::max(0, offset_y) as usize),
+        );
+        self.set(Props::ShadowColorKey, Value::Color(c));
+        self
+    }
+
+    fn get_shadow_offset(&self) -> (usize, usize) {
+        if self.get_as_color(Props::ShadowColorKey) == Hue::default() {
+            return (0, 0);
         }
+        (
+            self.get_as_int(Props::ShadowOffsetXKey),
+            self.get_as_int(Props::ShadowOffsetYKey),
+        )
+    }
 
-        // If the border is set and no side is specifically mentioned then apply borders to all sides.
-        if border != Border::default() && !(top_set || right_set || bottom_set || left_set) {
-            has_top = true;
-            has_right = true;
-            has_bottom = true;
-            has_left = true;
+    fn apply_shadow(&self, strs: &str) -> String {
+        let (offset_x, offset_y) = self.get_shadow_offset();
+        if offset_x == 0 && offset_y == 0 {
+            return strs.to_string();
         }
+        let mut block = Buffer::from_ansi(strs);
+        block.pad_lines();
 
-        let (lines, width) = get_lines(strs);
+        let mut shadow = Buffer::new(block.width, block.height());
+        let c = self.get_as_color(Props::ShadowColorKey);
+        shadow.fill(&mut WhiteSpace::new(&[with_whitespace_bg(c)]));
+
+        let mut compiled = Buffer::new(block.width + offset_x, block.height() + offset_y);
+        compiled.blit(&shadow, offset_x, offset_y);
+        compiled.blit(&block, 0, 0);
+        compiled.to_ansi()
+    }
+
+    // The border with the missing parts filled in and the corners of the missing sides removed.
+    // The corners and the sides are padded so that every line of the left and the right column
+    // is as wide as the widest piece in it, which are returned along with the border.
+    fn get_frame_border(&self) -> (Border, usize, usize) {
+        let mut border = self.get_border_style();
+        let (has_top, has_right, has_bottom, has_left) = self.get_border_sides();
 
         if has_left && border.left.is_empty() {
-            border.left = String::from(" ");
+            border.left = Cow::Borrowed(" ");
         }
 
         if has_right && border.right.is_empty() {
-            border.right = String::from(" ");
+            border.right = Cow::Borrowed(" ");
         }
 
         if has_top && has_left && border.top_left.is_empty() {
-            border.top_left = String::from(" ");
+            border.top_left = Cow::Borrowed(" ");
         }
 
         if has_top && has_right && border.top_right.is_empty() {
-            border.top_right = String::from(" ");
+            border.top_right = Cow::Borrowed(" ");
         }
 
         if has_bottom && has_left && border.bottom_left.is_empty() {
-            border.bottom_left = String::from(" ");
+            border.bottom_left = Cow::Borrowed(" ");
         }
 
         if has_bottom && has_right && border.bottom_right.is_empty() {
-            border.bottom_right = String::from(" ");
+            border.bottom_right = Cow::Borrowed(" ");
         }
 
-        if has_top {
-            match (has_left, has_right) {
-                (false, false) => {
-                    border.top_left = String::from("");
-                    border.top_right = String::from("");
-                }
-                (true, false) => {
-                    border.top_right = String::from("");
-                }
-                (false, true) => {
-                    border.top_left = String::from("");
-                }
-                _ => {}
-            }
+        if !has_left {
+            border.top_left = Cow::Borrowed("");
+            border.bottom_left = Cow::Borrowed("");
         }
 
-        if has_bottom {
-            match (has_left, has_right) {
-                (false, false) => {
-                    border.bottom_left = String::from("");
-                    border.bottom_right = String::from("");
-                }
-                (true, false) => {
-                    border.bottom_right = String::from("");
-                }
-                (false, true) => {
-                    border.bottom_left = String::from("");
-                }
-                _ => {}
-            }
+        if !has_right {
+            border.top_right = Cow::Borrowed("");
+            border.bottom_right = Cow::Borrowed("");
         }
 
-        border.top_left = get_first_char_as_string(&border.top_left);
-        border.top_right = get_first_char_as_string(&border.top_right);
-        border.bottom_left = get_first_char_as_string(&border.bottom_left);
-        border.bottom_right = get_first_char_as_string(&border.bottom_right);
+        let left_width = match has_left {
+            true => column_width(&border.top_left, &border.left, &border.bottom_left),
+            false => 0,
+        };
+        let right_width = match has_right {
+            true => column_width(&border.top_right, &border.right, &border.bottom_right),
+            false => 0,
+        };
+
+        // Short corners are stretched with their edge towards the middle.
+        border.top_left = pad_corner(border.top_left, &border.top, left_width, false);
+        border.bottom_left = pad_corner(border.bottom_left, &border.bottom, left_width, false);
+        border.top_right = pad_corner(border.top_right, &border.top, right_width, true);
+        border.bottom_right = pad_corner(border.bottom_right, &border.bottom, right_width, true);
+
+        (border, left_width, right_width)
+    }
+
+    fn apply_border(&self, strs: &str) -> String {
+        let (has_top, has_right, has_bottom, has_left) = self.get_border_sides();
+
+        let top_fg = self.get_as_color(Props::BorderTopForegroundKey);
+        let bottom_fg = self.get_as_color(Props::BorderBottomForegroundKey);
+        let right_fg = self.get_as_color(Props::BorderRightForegroundKey);
+        let left_fg = self.get_as_color(Props::BorderLeftForegroundKey);
+
+        let top_bg = self.get_as_color(Props::BorderTopBackgroundKey);
+        let right_bg = self.get_as_color(Props::BorderRightBackgroundKey);
+        let left_bg = self.get_as_color(Props::BorderLeftBackgroundKey);
+        let bottom_bg = self.get_as_color(Props::BorderBottomBackgroundKey);
+
+        if !(has_top || has_right || has_bottom || has_left) {
+            return strs.to_string();
+        }
+
+        let (lines, width) = get_lines(strs);
+        let (border, left_width, right_width) = self.get_frame_border();
+        let left_graphemes: Vec<&str> = border.left.graphemes(true).collect();
+        let right_graphemes: Vec<&str> = border.right.graphemes(true).collect();
 
         let mut compiled_string = String::new();
 
         if has_top {
-            let mut top =
-                render_horizontal_edge(&border.top_left, &border.top, &border.top_right, width);
-            top = style_border(&top, top_fg, top_bg);
+            let top = self.render_labelled_edge(
+                (&border.top_left, &border.top, &border.top_right),
+                width,
+                (top_fg, top_bg),
+                (
+                    Props::BorderTitleKey,
+                    Props::BorderTitlePositionKey,
+                    Props::BorderTitleStyleKey,
+                ),
+            );
             compiled_string.push_str(&top);
             compiled_string.push_str("\n");
         }
@@ -536,24 +892,23 @@ impl Style {
         let mut right_index = 0;
         for (i, line) in lines.clone().enumerate() {
             if has_left {
-                let left_chars: Vec<String> = border.left.chars().map(|c| c.to_string()).collect();
-                let r = &left_chars[left_index];
+                let r = left_graphemes[left_index];
                 left_index += 1;
-                if left_index >= left_chars.len() {
+                if left_index >= left_graphemes.len() {
                     left_index = 0;
                 }
-                compiled_string.push_str(&style_border(&r, left_fg, left_bg))
+                let sp = " ".repeat(left_width.saturating_sub(grapheme_width(r)));
+                compiled_string.push_str(&style_border(&format!("{}{}", r, sp), left_fg, left_bg))
             }
-            compiled_string.push_str(&line);
+            compiled_string.push_str(line);
             if has_right {
-                let right_chars: Vec<String> =
-                    border.right.chars().map(|c| c.to_string()).collect();
-                let r = &right_chars[right_index];
+                let r = right_graphemes[right_index];
                 right_index += 1;
-                if right_index >= right_chars.len() {
+                if right_index >= right_graphemes.len() {
                     right_index = 0;
                 }
-                compiled_string.push_str(&style_border(&r, right_fg, right_bg))
+                let sp = " ".repeat(right_width.saturating_sub(grapheme_width(r)));
+                compiled_string.push_str(&style_border(&format!("{}{}", sp, r), right_fg, right_bg))
             }
             if i < lines.clone().count() - 1 {
                 compiled_string.push_str("\n")
@@ -561,13 +916,16 @@ impl Style {
         }
 
         if has_bottom {
-            let mut bottom = render_horizontal_edge(
-                &border.bottom_left,
-                &border.bottom,
-                &border.bottom_right,
+            let bottom = self.render_labelled_edge(
+                (&border.bottom_left, &border.bottom, &border.bottom_right),
                 width,
+                (bottom_fg, bottom_bg),
+                (
+                    Props::BorderFooterKey,
+                    Props::BorderFooterPositionKey,
+                    Props::BorderFooterStyleKey,
+                ),
             );
-            bottom = style_border(&bottom, bottom_fg, bottom_bg);
             compiled_string.push_str("\n");
             compiled_string.push_str(&bottom);
         }
@@ -594,7 +952,48 @@ impl Style {
         }
     }
 
+    // Variants of the style for different widths. Each variant applies from its minimum number of
+    // columns up, and is merged over the style along with the variants for smaller widths when
+    // rendering with `render_in`.
+    pub fn responsive(mut self, variants: &[(usize, Style)]) -> Self {
+        let mut variants = variants.to_vec();
+        variants.sort_by_key(|(min_cols, _)| *min_cols);
+        self.set(Props::ResponsiveKey, Value::Responsive(variants));
+        self
+    }
+
+    // The style with the variants which apply at the given width merged into it.
+    pub fn resolve(&self, width: usize) -> Style {
+        let mut resolved = self.copy();
+        if let Some(Value::Responsive(variants)) = resolved.rules.remove(&Props::ResponsiveKey) {
+            for (_, variant) in variants.iter().filter(|(min_cols, _)| *min_cols <= width) {
+                for (key, value) in &variant.rules {
+                    resolved.rules.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        resolved
+    }
+
+    // Renders for the available width, picking the variants which fit in it.
+    pub fn render_in(&self, width: usize, strs: String) -> String {
+        self.resolve(width).render(strs)
+    }
+
     pub fn render(&self, strs: String) -> String {
+        if self.rules.is_empty() {
+            return expand_tabs(&format!("{}{}", self.value, strs), self.get_tab_width());
+        }
+        self.render_buffer(strs).to_ansi()
+    }
+
+    // The stages below still build the block as a string, which is parsed into cells once at the
+    // end. Writing it back out from the cells only emits the style changes between them.
+    pub fn render_buffer(&self, strs: String) -> Buffer {
+        Buffer::from_ansi(&self.render_ansi(strs))
+    }
+
+    fn render_ansi(&self, strs: String) -> String {
         // The final compiled string to be returned after all the operations.
         let mut compiled_string = String::new();
         compiled_string.push_str(&strs);
@@ -603,6 +1002,8 @@ impl Style {
             compiled_string = format!("{}{}", self.value, compiled_string);
         }
 
+        compiled_string = expand_tabs(&compiled_string, self.get_tab_width());
+
         if self.rules.len() == 0 {
             return compiled_string.to_string();
         }
@@ -629,6 +1030,8 @@ impl Style {
         let vertical_align = self.get_as_position(Props::AlignVerticalKey);
         let max_width = self.get_as_int(Props::MaxWidthKey);
         let max_height = self.get_as_int(Props::MaxHeightKey);
+        let min_width = self.get_as_int(Props::MinWidthKey);
+        let min_height = self.get_as_int(Props::MinHeightKey);
 
         // Padding related components
         let top_padding = self.get_as_int(Props::PaddingTopKey);
@@ -644,7 +1047,9 @@ impl Style {
             strikethrough && self.get_as_bool(Props::StrikethroughSpacesKey, true);
 
         let style_whitespace = reverse;
-        let use_space_styler = underline_spaces || strikethrough_spaces;
+        // The spaces are styled on their own whenever they may need to skip the underline or
+        // the strikethrough.
+        let use_space_styler = underline || strikethrough;
 
         if bold {
             te.push_str(&Attribute::Bold.to_string());
@@ -655,7 +1060,7 @@ impl Style {
         }
 
         if underline {
-            te.push_str(&Attribute::Underlined.to_string());
+            te.push_str(&self.get_underline_sgr());
         }
         if strikethrough {
             te.push_str(&Attribute::CrossedOut.to_string());
@@ -705,13 +1110,17 @@ impl Style {
         }
 
         if underline_spaces {
-            te_space.push_str(&Attribute::Underlined.to_string());
+            te_space.push_str(&self.get_underline_sgr());
         }
 
         if strikethrough_spaces {
             te_space.push_str(&Attribute::CrossedOut.to_string());
         }
 
+        if let Some(Value::Transform(f)) = self.rules.get(&Props::TransformKey) {
+            compiled_string = transform::apply(&compiled_string, *f);
+        }
+
         if inline {
             compiled_string = compiled_string.replace("\n", "");
         }
@@ -719,30 +1128,41 @@ impl Style {
         // Word wrap feature.
         // TODO: Handle the case of text wrapping with hyphenation.
         if !inline && width > 0 {
-            let wrap_at = (width - left_padding - right_padding) as usize;
-            compiled_string = textwrap::fill(&compiled_string, wrap_at);
+            let wrap_at = cmp::max(1, width.saturating_sub(left_padding + right_padding));
+            compiled_string = measure::fill(&compiled_string, wrap_at);
         }
 
         // Rendering the core text here. Inside a code block to delete the temp values
         // once it goes out of scope.
         {
             let mut temp = String::new();
+            let (link_open, link_close) = self.get_hyperlink().unwrap_or_default();
             let l = compiled_string.split("\n");
             for (i, line) in l.clone().enumerate() {
+                temp.push_str(&link_open);
                 // Identify the spaces and applying the styling separately to the spaces.
                 // This only works for underscores and strikethroughs
                 if use_space_styler {
-                    for ch in line.chars() {
-                        if ch.is_whitespace() {
-                            write!(temp, "{}{}{}", te_space, ch, Attribute::Reset.to_string())
-                                .unwrap();
-                            continue;
+                    // Only switch the styles when going from spaces to text and back.
+                    let mut in_space = None;
+                    for g in line.graphemes(true) {
+                        let is_space = g.chars().all(char::is_whitespace);
+                        if in_space != Some(is_space) {
+                            if in_space.is_some() {
+                                write!(temp, "{}", Attribute::Reset).unwrap();
+                            }
+                            write!(temp, "{}", if is_space { &te_space } else { &te }).unwrap();
+                            in_space = Some(is_space);
                         }
-                        write!(temp, "{}{}{}", te, ch, Attribute::Reset.to_string()).unwrap();
+                        temp.push_str(g);
                     }
+                    write!(temp, "{}", Attribute::Reset).unwrap();
+                } else if te.is_empty() {
+                    temp.push_str(line);
                 } else {
                     write!(temp, "{}{}{}", te, line, Attribute::Reset.to_string(),).unwrap()
                 }
+                temp.push_str(&link_close);
 
                 if i != l.clone().count() - 1 {
                     write!(temp, "\n").unwrap();
@@ -751,27 +1171,38 @@ impl Style {
             compiled_string = temp.to_string();
         }
 
+        // The minimum sizes only add space around the content, they never wrap it.
+        let height = cmp::max(height, min_height);
+        let width = cmp::max(width, min_width);
+
         if !inline {
-            if left_padding > 0 {
-                let mut style: Option<&String> = None;
-                if color_whitespaces || style_whitespace {
-                    style = Some(&te_white_space);
-                }
-                compiled_string = pad_left(&compiled_string, left_padding, style);
-            }
-            if right_padding > 0 {
-                let mut style: Option<&String> = None;
-                if color_whitespaces || style_whitespace {
-                    style = Some(&te_white_space);
-                }
-                compiled_string = pad_right(&compiled_string, left_padding, style);
+            let mut style = String::new();
+            if color_whitespaces || style_whitespace {
+                style = te_white_space.to_string();
             }
-            if top_padding > 0 {
-                compiled_string = pad_top(&compiled_string, top_padding)
-            }
-            if bottom_padding > 0 {
-                compiled_string = pad_bottom(&compiled_string, bottom_padding);
+            // The content is aligned within the size left inside the padding first, so that the
+            // padding goes around the whole block.
+            if height > 0 {
+                compiled_string = align_text_vertical(
+                    &mut compiled_string,
+                    vertical_align,
+                    height.saturating_sub(top_padding + bottom_padding),
+                );
             }
+            compiled_string = align_text_horizontal(
+                &compiled_string,
+                horizontal_align,
+                width.saturating_sub(left_padding + right_padding),
+                Some(&style),
+            );
+            let mut ws = WhiteSpace {
+                style,
+                chars: self.get_as_string(Props::PaddingCharsKey),
+            };
+            compiled_string = pad_left(&compiled_string, left_padding, &mut ws);
+            compiled_string = pad_right(&compiled_string, right_padding, &mut ws);
+            compiled_string = pad_top(&compiled_string, top_padding, &mut ws);
+            compiled_string = pad_bottom(&compiled_string, bottom_padding, &mut ws);
         }
 
         if height > 0 {
@@ -793,6 +1224,7 @@ impl Style {
 
         if !inline {
             compiled_string = self.apply_border(&compiled_string);
+            compiled_string = self.apply_shadow(&compiled_string);
             compiled_string = self.apply_margins(&compiled_string, inline);
         }
 
@@ -940,29 +1372,161 @@ fn which_sides_color(values: &[Hue]) -> (Hue, Hue, Hue, Hue) {
 }
 
 fn truncated_string(strs: &str, max_width: usize) -> String {
-    let mut current_width = 0;
-    let mut truncated_string = String::new();
-    let mut in_escape_seq = false;
-
-    for ch in strs.chars() {
-        if ch == '\x1b' {
-            in_escape_seq = true;
-            truncated_string.push(ch);
+    let mut lines = vec![];
+    for line in strs.split('\n') {
+        let mut current_width = 0;
+        let mut truncated_line = String::new();
+
+        for token in tokenize(line) {
+            match token {
+                // Escape sequences are kept even after the cut so that the trailing resets still apply.
+                Token::Escape(e) => truncated_line.push_str(e),
+                Token::Char(g, w) => {
+                    // Once a grapheme doesn't fit, nothing after it is kept either.
+                    if current_width + w > max_width {
+                        current_width = max_width + 1;
+                        continue;
+                    }
+                    truncated_line.push_str(g);
+                    current_width += w;
+                }
+            }
         }
+        lines.push(truncated_line);
+    }
+    lines.join("\n")
+}
 
-        if !in_escape_seq && current_width < max_width {
-            truncated_string.push(ch);
-            current_width += 1;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::style::Color;
 
-        if ch == 'm' {
-            in_escape_seq = false;
-            truncated_string.push(ch);
-        }
+    const RED: Color = Color::Rgb { r: 255, g: 0, b: 0 };
 
-        if current_width >= max_width {
-            break;
-        }
+    fn render(style: Style, strs: &str) -> Buffer {
+        style.render_buffer(strs.to_string())
+    }
+
+    #[test]
+    fn unstyled_blocks_have_no_escapes() {
+        let rendered = Style::new_style()
+            .border(crate::border::NORMAL, &[true])
+            .padding(&[0, 1])
+            .align(&[Position::Center])
+            .render("ab\nc".to_string());
+        assert_eq!(rendered, "┌────┐\n│ ab │\n│ c  │\n└────┘");
+    }
+
+    #[test]
+    fn padding_chars_surround_the_whole_block() {
+        let rendered = Style::new_style()
+            .padding(&[1])
+            .padding_chars(".")
+            .width(6)
+            .render("ab".to_string());
+        assert_eq!(rendered, "......\n.ab  .\n......");
+    }
+
+    #[test]
+    fn padding_rows_follow_the_height() {
+        let rendered = Style::new_style()
+            .padding(&[1, 1])
+            .padding_chars("-=")
+            .width(6)
+            .height(4)
+            .align(&[Position::Right, Position::Bottom])
+            .render("ab".to_string());
+        assert_eq!(rendered, "-=-=-=\n-    -\n-  ab-\n-=-=-=");
+    }
+
+    #[test]
+    fn margin_chars_fill_every_side() {
+        let rendered = Style::new_style()
+            .padding(&[0, 1])
+            .margin(&[1, 2])
+            .margin_chars("░")
+            .render("ab".to_string());
+        assert_eq!(rendered, "░░░░░░░░\n░░ ab ░░\n░░░░░░░░");
+    }
+
+    #[test]
+    fn border_labels_only_use_their_first_line() {
+        let rendered = Style::new_style()
+            .border(crate::border::NORMAL, &[true])
+            .border_title("ab\ncd", Position::Left)
+            .border_footer("\nef", Position::Left)
+            .render("123456".to_string());
+        assert_eq!(rendered, "┌─ ab ─┐\n│123456│\n└──────┘");
+    }
+
+    #[test]
+    fn inline_removes_newlines() {
+        let rendered = Style::new_style()
+            .bold(true)
+            .inline(true)
+            .render("a\nb".to_string());
+        assert_eq!(measure::strip_ansi(&rendered), "ab");
+    }
+
+    #[test]
+    fn margin_background_fills_every_side() {
+        let style = Style::new_style()
+            .margin(&[1])
+            .margin_background(Hue::from("#FF0000"));
+        let buffer = render(style, "a");
+        assert_eq!(buffer.height(), 3);
+        for (y, x) in [(0, 0), (0, 2), (1, 0), (1, 2), (2, 1)] {
+            assert_eq!(buffer.lines[y][x].style.bg, Some(RED), "cell {} {}", x, y);
+        }
+        assert_eq!(buffer.lines[1][1].symbol, "a");
+        assert_eq!(buffer.lines[1][1].style.bg, None);
+    }
+
+    #[test]
+    fn color_whitespace_controls_the_padding_background() {
+        let style = Style::new_style()
+            .background(Hue::from("#FF0000"))
+            .padding(&[0, 1]);
+        let colored = render(style.copy(), "a");
+        assert_eq!(colored.lines[0][0].style.bg, Some(RED));
+        assert_eq!(colored.lines[0][2].style.bg, Some(RED));
+
+        let plain = render(style.color_whitespace(false), "a");
+        assert_eq!(plain.lines[0][0].style.bg, None);
+        assert_eq!(plain.lines[0][1].style.bg, Some(RED));
+        assert_eq!(plain.lines[0][2].style.bg, None);
+    }
+
+    #[test]
+    fn underline_spaces_can_be_turned_off() {
+        let underlined = |style: Style| {
+            render(style, "a b").lines[0]
+                .iter()
+                .map(|c| c.style.attributes.has(Attribute::Underlined))
+                .collect::<Vec<_>>()
+        };
+        let style = Style::new_style().underline(true);
+        assert_eq!(underlined(style.copy()), [true, true, true]);
+        assert_eq!(
+            underlined(style.underline_spaces(false)),
+            [true, false, true]
+        );
+    }
+
+    #[test]
+    fn strikethrough_spaces_can_be_turned_off() {
+        let crossed = |style: Style| {
+            render(style, "a b").lines[0]
+                .iter()
+                .map(|c| c.style.attributes.has(Attribute::CrossedOut))
+                .collect::<Vec<_>>()
+        };
+        let style = Style::new_style().strikethrough(true);
+        assert_eq!(crossed(style.copy()), [true, true, true]);
+        assert_eq!(
+            crossed(style.strikethrough_spaces(false)),
+            [true, false, true]
+        );
     }
-    truncated_string
 }
@@ -0,0 +1,111 @@
+use crate::ansi::{tokenize, Token};
+
+const SMALL_CAPS: [char; 26] = [
+    'ᴀ', 'ʙ', 'ᴄ', 'ᴅ', 'ᴇ', 'ꜰ', 'ɢ', 'ʜ', 'ɪ', 'ᴊ', 'ᴋ', 'ʟ', 'ᴍ', 'ɴ', 'ᴏ', 'ᴘ', 'ǫ', 'ʀ', 'ꜱ',
+    'ᴛ', 'ᴜ', 'ᴠ', 'ᴡ', 'x', 'ʏ', 'ᴢ',
+];
+
+pub fn uppercase(strs: &str) -> String {
+    strs.to_uppercase()
+}
+
+pub fn lowercase(strs: &str) -> String {
+    strs.to_lowercase()
+}
+
+// Upper cases the first letter of every word and lower cases the rest.
+pub fn title_case(strs: &str) -> String {
+    let mut b = String::new();
+    let mut word_start = true;
+    for ch in strs.chars() {
+        if ch.is_alphanumeric() {
+            if word_start {
+                b.extend(ch.to_uppercase());
+            } else {
+                b.extend(ch.to_lowercase());
+            }
+            word_start = false;
+        } else {
+            b.push(ch);
+            word_start = ch.is_whitespace() || ch == '-';
+        }
+    }
+    b
+}
+
+// Swaps the lower case latin letters for their small capital forms. Unicode has no small capital
+// x, so it is kept as it is.
+pub fn small_caps(strs: &str) -> String {
+    strs.chars()
+        .map(|ch| match ch {
+            'a'..='z' => SMALL_CAPS[(ch as u8 - b'a') as usize],
+            _ => ch,
+        })
+        .collect()
+}
+
+// Runs the transform over the text with the escape sequences left out, so that state such as the
+// start of a word carries across them, and puts the sequences back at the same chars. Transforms
+// which change the number of chars, like upper casing `ß` to `SS`, fall back to running over the
+// text between the sequences on its own.
+pub fn apply(strs: &str, transform: fn(&str) -> String) -> String {
+    let mut text = String::new();
+    let mut escapes: Vec<(usize, &str)> = vec![];
+    for token in tokenize(strs) {
+        match token {
+            Token::Escape(e) => escapes.push((text.chars().count(), e)),
+            Token::Char(g, _) => text.push_str(g),
+        }
+    }
+
+    let transformed = transform(&text);
+    if transformed.chars().count() != text.chars().count() {
+        return apply_between(strs, transform);
+    }
+
+    let mut b = String::new();
+    let mut escapes = escapes.into_iter().peekable();
+    for (i, ch) in transformed.chars().enumerate() {
+        while let Some((_, e)) = escapes.next_if(|(at, _)| *at == i) {
+            b.push_str(e);
+        }
+        b.push(ch);
+    }
+    escapes.for_each(|(_, e)| b.push_str(e));
+    b
+}
+
+fn apply_between(strs: &str, transform: fn(&str) -> String) -> String {
+    let mut b = String::new();
+    let mut text = String::new();
+    for token in tokenize(strs) {
+        match token {
+            Token::Escape(e) => {
+                b.push_str(&transform(&text));
+                text.clear();
+                b.push_str(e);
+            }
+            Token::Char(g, _) => text.push_str(g),
+        }
+    }
+    b.push_str(&transform(&text));
+    b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_starts_carry_across_escapes() {
+        assert_eq!(
+            apply("\x1b[1mhello\x1b[0mworld again", title_case),
+            "\x1b[1mHello\x1b[0mworld Again"
+        );
+    }
+
+    #[test]
+    fn escapes_stay_when_the_length_changes() {
+        assert_eq!(apply("\x1b[1mß\x1b[0mx", uppercase), "\x1b[1mSS\x1b[0mX");
+    }
+}
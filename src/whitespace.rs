@@ -1,13 +1,19 @@
 use crossterm::style::{Attribute, SetBackgroundColor, SetForegroundColor};
-use textwrap::core::display_width;
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::{color::ColorValue, Hue};
+use crate::{
+    ansi::{tokenize, Token},
+    color::ColorValue,
+    measure::grapheme_width,
+    Hue,
+};
 
 pub struct WhiteSpace {
     pub style: String,
     pub chars: String,
 }
 
+#[derive(Clone)]
 pub enum WhiteSpaceType {
     Style(String),
     Chars(String),
@@ -27,34 +33,47 @@ impl WhiteSpace {
     }
 
     pub fn render(&mut self, width: usize) -> String {
+        if width == 0 {
+            return String::new();
+        }
         if self.chars.is_empty() {
             self.chars = String::from(" ");
         }
 
-        let r: Vec<String> = self.chars.chars().map(|c| c.to_string()).collect();
+        let mut r: Vec<&str> = self
+            .chars
+            .graphemes(true)
+            .filter(|g| grapheme_width(g) > 0)
+            .collect();
+        if r.is_empty() {
+            r.push(" ");
+        }
         let mut j = 0;
         let mut i = 0;
 
-        let mut b = format!("{}", &self.style);
+        let mut b = self.style.to_string();
 
         while i < width {
-            b.push_str(&r[j]);
+            // Wide chars which don't fit in the remaining space are replaced with spaces below.
+            let w = grapheme_width(r[j]);
+            if i + w > width {
+                break;
+            }
+            b.push_str(r[j]);
+            i += w;
             j += 1;
             if j >= r.len() {
                 j = 0;
             }
-            i += display_width(&r[j]);
         }
 
-        if display_width(&b) <= width {
-            let short = width - display_width(&b);
-            if short > 0 {
-                let s = " ".repeat(short);
-                b.push_str(&s);
-            }
+        if i < width {
+            b.push_str(&" ".repeat(width - i));
+        }
+        if !self.style.is_empty() {
+            b.push_str(&Attribute::Reset.to_string());
         }
-        b.push_str(&Attribute::Reset.to_string());
-        b.to_string()
+        b
     }
 }
 
@@ -77,3 +96,31 @@ pub fn with_whitespace_fg(c: Hue) -> WhiteSpaceType {
     }
     WhiteSpaceType::Style(b)
 }
+
+// Replaces the tabs with spaces up to the next tab stop, the stops being `tab_width` columns apart.
+// Escape sequences take up no columns. A tab width of 0 removes the tabs.
+pub fn expand_tabs(strs: &str, tab_width: usize) -> String {
+    let mut lines = vec![];
+    for line in strs.split('\n') {
+        let mut column = 0;
+        let mut expanded = String::new();
+        for token in tokenize(line) {
+            match token {
+                Token::Escape(e) => expanded.push_str(e),
+                Token::Char("\t", _) => {
+                    if tab_width > 0 {
+                        let n = tab_width - column % tab_width;
+                        expanded.push_str(&" ".repeat(n));
+                        column += n;
+                    }
+                }
+                Token::Char(g, w) => {
+                    expanded.push_str(g);
+                    column += w;
+                }
+            }
+        }
+        lines.push(expanded);
+    }
+    lines.join("\n")
+}